@@ -0,0 +1,364 @@
+//! Lines written for a terminal: ANSI escape sequences, and carriage returns
+//! drawing progress bars over themselves
+use ratatui::style::{Color, Modifier, Style};
+use std::ops::Range;
+
+/// How the escape sequences of the logs are shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnsiMode {
+    /// Colours and attributes applied, other sequences removed
+    Render,
+    /// Every sequence removed
+    Strip,
+    /// Sequences and control characters shown as text, like `^[[31m`
+    Raw,
+}
+
+impl AnsiMode {
+    pub fn next(self) -> Self {
+        match self {
+            AnsiMode::Render => AnsiMode::Strip,
+            AnsiMode::Strip => AnsiMode::Raw,
+            AnsiMode::Raw => AnsiMode::Render,
+        }
+    }
+}
+
+/// Text of the line as a terminal shows it, and the styles of its characters
+pub type StyledLine = (String, Vec<(Range<usize>, Style)>);
+
+/// Line as shown in the mode. Unless raw, carriage returns and backspaces
+/// move back over the line, leaving only what was written last, like the
+/// final state of a tqdm bar.
+pub fn parse_line(line: &str, mode: AnsiMode) -> StyledLine {
+    if mode == AnsiMode::Raw {
+        return (caret_notation(line), vec![]);
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let mut cells: Vec<(char, Style)> = vec![];
+    let mut position = 0;
+    let mut style = Style::default();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            '\x1b' => {
+                let (end, sequence) = escape_sequence(&chars, i);
+                i = end;
+                match sequence {
+                    Sequence::Sgr(params) if mode == AnsiMode::Render => {
+                        style = apply_sgr(style, &params);
+                    }
+                    Sequence::EraseLine(0) => cells.truncate(position),
+                    Sequence::EraseLine(1) => {
+                        for cell in cells.iter_mut().take(position) {
+                            *cell = (' ', Style::default());
+                        }
+                    }
+                    Sequence::EraseLine(2) => cells.clear(),
+                    _ => (),
+                }
+            }
+            '\r' => position = 0,
+            '\x08' => position = position.saturating_sub(1),
+            c if c.is_control() && c != '\t' => (),
+            c => {
+                match cells.get_mut(position) {
+                    Some(cell) => *cell = (c, style),
+                    None => {
+                        // erased characters before the position are blank
+                        cells.resize(position, (' ', Style::default()));
+                        cells.push((c, style));
+                    }
+                }
+                position += 1;
+            }
+        }
+    }
+    let text = cells.iter().map(|(c, _)| c).collect();
+    let mut styles: Vec<(Range<usize>, Style)> = vec![];
+    for (i, &(_, style)) in cells.iter().enumerate() {
+        match styles.last_mut() {
+            Some((range, last)) if *last == style && range.end == i => range.end = i + 1,
+            _ if style == Style::default() => (),
+            _ => styles.push((i..i + 1, style)),
+        }
+    }
+    (text, styles)
+}
+
+/// Control characters written as `^[`, `^M`...
+fn caret_notation(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    for c in line.chars() {
+        match c {
+            '\t' => text.push(c),
+            '\x7f' => text.push_str("^?"),
+            c if (c as u32) < 0x20 => {
+                text.push('^');
+                text.push((c as u8 + b'@') as char);
+            }
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+enum Sequence {
+    /// Select Graphic Rendition, setting colours and attributes. Each
+    /// parameter holds its sub-parameters, written after colons.
+    Sgr(Vec<Vec<u16>>),
+    /// Erases after the cursor, before it, or the whole line
+    EraseLine(u16),
+    Other,
+}
+
+/// Sequence after an escape at `start`, with the index following it. A
+/// sequence cut by the end of the line is dropped.
+fn escape_sequence(chars: &[char], start: usize) -> (usize, Sequence) {
+    match chars.get(start) {
+        // control sequence: parameters, intermediates, then a final byte
+        Some('[') => {
+            let mut i = start + 1;
+            while i < chars.len() && ('\x30'..='\x3f').contains(&chars[i]) {
+                i += 1;
+            }
+            let params: String = chars[start + 1..i].iter().collect();
+            while i < chars.len() && ('\x20'..='\x2f').contains(&chars[i]) {
+                i += 1;
+            }
+            let Some(&last) = chars.get(i) else {
+                return (chars.len(), Sequence::Other);
+            };
+            let numbers: Vec<Vec<u16>> = params
+                .split(';')
+                .map(|param| param.split(':').map(|n| n.parse().unwrap_or(0)).collect())
+                .collect();
+            let sequence = match last {
+                'm' => Sequence::Sgr(numbers),
+                'K' => Sequence::EraseLine(numbers[0][0]),
+                _ => Sequence::Other,
+            };
+            (i + 1, sequence)
+        }
+        // operating system command, like a window title, ended by BEL or ST
+        Some(']') => {
+            let mut i = start + 1;
+            while i < chars.len() {
+                match chars[i] {
+                    '\x07' => return (i + 1, Sequence::Other),
+                    '\x1b' if chars.get(i + 1) == Some(&'\\') => return (i + 2, Sequence::Other),
+                    _ => i += 1,
+                }
+            }
+            (i, Sequence::Other)
+        }
+        // charset designation, like `ESC ( B`
+        Some(c) if ('\x20'..='\x2f').contains(c) => ((start + 2).min(chars.len()), Sequence::Other),
+        Some(_) => (start + 1, Sequence::Other),
+        None => (start, Sequence::Other),
+    }
+}
+
+/// Style after the SGR parameters, `ESC[m` being read as `ESC[0m`
+fn apply_sgr(mut style: Style, params: &[Vec<u16>]) -> Style {
+    let mut params = params.iter();
+    while let Some(param) = params.next() {
+        let (&first, subparams) = param.split_first().expect("split always yields a part");
+        style = match first {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            6 => style.add_modifier(Modifier::RAPID_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            8 => style.add_modifier(Modifier::HIDDEN),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            21 | 22 => without(style, Modifier::BOLD | Modifier::DIM),
+            23 => without(style, Modifier::ITALIC),
+            24 => without(style, Modifier::UNDERLINED),
+            25 => without(style, Modifier::SLOW_BLINK | Modifier::RAPID_BLINK),
+            27 => without(style, Modifier::REVERSED),
+            28 => without(style, Modifier::HIDDEN),
+            29 => without(style, Modifier::CROSSED_OUT),
+            30..=37 => style.fg(basic_color(first - 30)),
+            90..=97 => style.fg(bright_color(first - 90)),
+            40..=47 => style.bg(basic_color(first - 40)),
+            100..=107 => style.bg(bright_color(first - 100)),
+            38 | 48 => match extended_color(subparams, &mut params) {
+                Some(color) if first == 38 => style.fg(color),
+                Some(color) => style.bg(color),
+                None => style,
+            },
+            39 => Style { fg: None, ..style },
+            49 => Style { bg: None, ..style },
+            _ => style,
+        };
+    }
+    style
+}
+
+/// Style with the attributes turned off, not recorded as removed, so that
+/// text written after them is plain
+fn without(style: Style, modifier: Modifier) -> Style {
+    Style {
+        add_modifier: style.add_modifier - modifier,
+        ..style
+    }
+}
+
+/// Colour of `38;5;n` or `38;2;r;g;b`, taking the parameters following 38,
+/// or of the colon forms `38:5:n`, `38:2:r:g:b` and `38:2:id:r:g:b`, from its
+/// sub-parameters
+fn extended_color<'p>(
+    subparams: &[u16],
+    params: &mut impl Iterator<Item = &'p Vec<u16>>,
+) -> Option<Color> {
+    let rgb = |r: u16, g: u16, b: u16| Color::Rgb(r as u8, g as u8, b as u8);
+    match subparams {
+        [5, n] => Some(Color::Indexed(*n as u8)),
+        [2, r, g, b] | [2, _, r, g, b] => Some(rgb(*r, *g, *b)),
+        [] => {
+            let mut next = || params.next().map(|param| param[0]);
+            match next()? {
+                5 => Some(Color::Indexed(next()? as u8)),
+                2 => Some(rgb(next()?, next()?, next()?)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn basic_color(index: u16) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        _ => Color::Gray,
+    }
+}
+
+fn bright_color(index: u16) -> Color {
+    match index {
+        0 => Color::DarkGray,
+        1 => Color::LightRed,
+        2 => Color::LightGreen,
+        3 => Color::LightYellow,
+        4 => Color::LightBlue,
+        5 => Color::LightMagenta,
+        6 => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(line: &str) -> StyledLine {
+        parse_line(line, AnsiMode::Render)
+    }
+
+    fn fg(color: Color) -> Style {
+        Style::default().fg(color)
+    }
+
+    #[test]
+    fn basic_and_bright_colours() {
+        let (text, styles) = render("\x1b[31mred\x1b[0m \x1b[1;94mblue\x1b[m");
+        assert_eq!(text, "red blue");
+        assert_eq!(
+            styles,
+            [
+                (0..3, fg(Color::Red)),
+                (4..8, fg(Color::LightBlue).add_modifier(Modifier::BOLD)),
+            ]
+        );
+        let (_, styles) = render("\x1b[41;32mx\x1b[39my\x1b[49mz");
+        let bg = Style::default().bg(Color::Red);
+        assert_eq!(styles, [(0..1, bg.fg(Color::Green)), (1..2, bg)]);
+    }
+
+    #[test]
+    fn extended_colours() {
+        let colors = [
+            ("\x1b[38;5;208mx", fg(Color::Indexed(208))),
+            ("\x1b[38;2;1;2;3mx", fg(Color::Rgb(1, 2, 3))),
+            ("\x1b[38:5:208mx", fg(Color::Indexed(208))),
+            ("\x1b[38:2:1:2:3mx", fg(Color::Rgb(1, 2, 3))),
+            // the colon form with an empty colour space id
+            ("\x1b[38:2::1:2:3mx", fg(Color::Rgb(1, 2, 3))),
+            (
+                "\x1b[48:2::1:2:3mx",
+                Style::default().bg(Color::Rgb(1, 2, 3)),
+            ),
+        ];
+        for (line, style) in colors {
+            assert_eq!(render(line).1, [(0..1, style)], "{:?}", line);
+        }
+        // the parameters after a colon colour are not read as attributes
+        let (_, styles) = render("\x1b[38:2::1:2:3;4mx");
+        let underlined = fg(Color::Rgb(1, 2, 3)).add_modifier(Modifier::UNDERLINED);
+        assert_eq!(styles, [(0..1, underlined)]);
+        // an incomplete colour is ignored
+        assert_eq!(render("\x1b[38;5mx").1, []);
+    }
+
+    #[test]
+    fn attributes_removed() {
+        let (_, styles) = render("\x1b[1;3ma\x1b[22mb\x1b[23mc");
+        let italic = Style::default().add_modifier(Modifier::ITALIC);
+        assert_eq!(
+            styles,
+            [(0..1, italic.add_modifier(Modifier::BOLD)), (1..2, italic)]
+        );
+    }
+
+    #[test]
+    fn carriage_returns_keep_the_last_write() {
+        let bar = "  0%|          |\r 50%|#####     |\r100%|##########|";
+        assert_eq!(render(bar).0, "100%|##########|");
+        // a shorter write leaves the end of the previous one
+        assert_eq!(render("downloading\rdone").0, "doneloading");
+        assert_eq!(render("ab\x08c").0, "ac");
+    }
+
+    #[test]
+    fn erased_lines() {
+        assert_eq!(render("downloading\r\x1b[Kdone").0, "done");
+        assert_eq!(render("downloading\r\x1b[2Kdone").0, "done");
+        assert_eq!(render("abcd\x08\x08\x1b[1Kx").0, "  xd");
+    }
+
+    #[test]
+    fn other_sequences_removed() {
+        assert_eq!(render("\x1b]0;title\x07text").0, "text");
+        assert_eq!(render("\x1b]0;title\x1b\\text").0, "text");
+        assert_eq!(render("\x1b(Btext\x1b[?25l").0, "text");
+        // a sequence cut at the end of the line is dropped
+        assert_eq!(render("text\x1b[3").0, "text");
+        assert_eq!(render("text\x1b").0, "text");
+    }
+
+    #[test]
+    fn strip_and_raw_modes() {
+        let line = "\x1b[31mred\x1b[0m\r";
+        assert_eq!(
+            parse_line(line, AnsiMode::Strip),
+            ("red".to_string(), vec![])
+        );
+        assert_eq!(
+            parse_line(line, AnsiMode::Raw),
+            ("^[[31mred^[[0m^M".to_string(), vec![])
+        );
+        assert_eq!(parse_line("a\tb\x7f", AnsiMode::Raw).0, "a\tb^?");
+    }
+}
@@ -1,20 +1,35 @@
+use crate::config::Config;
+use crate::editor::Editor;
 use crate::job_query_info::JobQueryInfo;
 use crate::job_query_info::JobTime;
+use crate::jobs::job_action::{self, JobAction, ScontrolCommand};
 use crate::jobs::job_handler;
+use crate::jobs::job_id::JobId;
 use crate::jobs::job_parser::JobFields;
+use crate::jobs::job_source;
+use crate::jobs::job_source::{JobDetails, TimeBound};
+use crate::jobs::log_page::{LogPage, PageRange};
+use crate::log_highlight::LogHighlighter;
+use crate::parser;
+use crate::prompt::{Prompt, PromptAction, PromptResult};
+use crate::submit::{Directive, SubmitForm};
+use crate::ui;
+use crate::worker::{Request, Response, Worker};
 use crate::Cli;
-use crate::{editor::Editor, jobs::job_parser};
-use chrono::NaiveDateTime;
 use color_eyre::eyre::{Ok, Report, Result};
 use core::panic;
 use crossterm::event::KeyCode;
+use std::time::{Duration, Instant};
 use tracing::info;
 
 pub enum DisplayState<'a> {
     Empty,
     Jobs(JobQueryInfo),
     Logs(Vec<String>),
-    Editor(Editor<'a>),
+    Editor(Box<Editor<'a>>),
+    Details(JobDetails),
+    Scripts(Vec<String>),
+    Submit(SubmitForm),
 }
 
 #[derive(Clone)]
@@ -22,59 +37,274 @@ pub struct MyPopup {
     pub popup_text: String,
 }
 
-#[derive(Clone, Copy)]
+/// Action waiting for the user to confirm it
+pub struct Confirm {
+    pub action: JobAction,
+    /// Same action restricted to the pending tasks, for arrays
+    pub pending_action: Option<JobAction>,
+}
+
+impl Confirm {
+    fn new(action: JobAction, array: bool) -> Self {
+        let pending_action = match action {
+            JobAction::Cancel {
+                ref job_id,
+                signal: None,
+                ..
+            } if array => Some(JobAction::Cancel {
+                job_id: job_id.clone(),
+                signal: None,
+                pending_only: true,
+            }),
+            _ => None,
+        };
+        Confirm {
+            action,
+            pending_action,
+        }
+    }
+
+    pub fn legend(&self) -> &'static str {
+        match self.pending_action {
+            Some(_) => "[y]es [p]ending tasks only [n]o",
+            None => "[y]es [n]o",
+        }
+    }
+}
+
+/// scontrol commands offered for a job, those that make sense in its state
+pub struct ActionMenu {
+    pub job_id: String,
+    pub commands: Vec<ScontrolCommand>,
+}
+
+impl ActionMenu {
+    pub fn text(&self) -> String {
+        self.commands
+            .iter()
+            .map(|command| format!("[{}]{}", command.key(), command.name()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FetchTime {
     Today,
     ThreeDaysAgo,
     AWeekAgo,
+    TwoWeeksAgo,
+    AMonthAgo,
+    ThreeMonthsAgo,
     SpecificWindow {
-        start: NaiveDateTime,
-        end: NaiveDateTime,
+        start: TimeBound,
+        end: Option<TimeBound>,
     },
 }
 
 impl FetchTime {
+    /// Wider window, or the window of the same span ending where a specific
+    /// one starts
     fn older(&self) -> FetchTime {
-        match self {
+        match *self {
             Today => ThreeDaysAgo,
             ThreeDaysAgo => AWeekAgo,
-            AWeekAgo => AWeekAgo,
-            _ => *self,
+            AWeekAgo => TwoWeeksAgo,
+            TwoWeeksAgo => AMonthAgo,
+            AMonthAgo => ThreeMonthsAgo,
+            ThreeMonthsAgo => ThreeMonthsAgo,
+            SpecificWindow { start, end } => SpecificWindow {
+                start: start.earlier(window_hours(start, end)),
+                end: Some(start),
+            },
         }
     }
+
+    /// Narrower window, or the window of the same span starting where a
+    /// specific one ends, cut at the current time
     fn newer(&self) -> FetchTime {
-        match self {
+        match *self {
             Today => Today,
             ThreeDaysAgo => Today,
             AWeekAgo => ThreeDaysAgo,
-            _ => *self,
+            TwoWeeksAgo => AWeekAgo,
+            AMonthAgo => TwoWeeksAgo,
+            ThreeMonthsAgo => AMonthAgo,
+            SpecificWindow {
+                start,
+                end: Some(end),
+            } => SpecificWindow {
+                start: end,
+                end: end.later(window_hours(start, Some(end))),
+            },
+            SpecificWindow { end: None, .. } => *self,
+        }
+    }
+
+    /// Parses a window typed as `<start>..<end>` or `<start>`, an empty text meaning today
+    fn from_range(range: &str) -> Result<FetchTime> {
+        if range.trim().is_empty() {
+            return Ok(Today);
+        }
+        let (start, end) = match range.split_once("..") {
+            Some((start, end)) if !end.trim().is_empty() => (start, Some(end)),
+            Some((start, _)) => (start, None),
+            None => (range, None),
+        };
+        let start = parser::parse_time_bound(start).map_err(Report::msg)?;
+        let end = end
+            .map(parser::parse_time_bound)
+            .transpose()
+            .map_err(Report::msg)?;
+        parser::check_time_window(start, end).map_err(Report::msg)?;
+        Ok(SpecificWindow { start, end })
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Today => "last 24 hours".to_string(),
+            ThreeDaysAgo => "last 3 days".to_string(),
+            AWeekAgo => "last week".to_string(),
+            TwoWeeksAgo => "last 2 weeks".to_string(),
+            AMonthAgo => "last month".to_string(),
+            ThreeMonthsAgo => "last 3 months".to_string(),
+            SpecificWindow { start, end } => format!(
+                "{} to {}",
+                start.display(),
+                end.map_or("now".to_string(), |end| end.display())
+            ),
         }
     }
 }
 
 use FetchTime::*;
 
+/// Id of the job a row stands for, slurm refusing job actions on a step
+fn action_target(job_fields: &JobFields) -> Result<JobId, String> {
+    match job_fields.id() {
+        Some(id) if id.is_step() => Err(format!(
+            "{} is a step: select its job {} to act on it.",
+            id,
+            id.job()
+        )),
+        Some(id) => Result::Ok(id),
+        None => Err(format!("Cannot act on job {}.", job_fields.job_id)),
+    }
+}
+
+/// Hours between the bounds, at least one, the end being now when missing
+fn window_hours(start: TimeBound, end: Option<TimeBound>) -> u32 {
+    let end = end.unwrap_or(TimeBound::HoursAgo(0));
+    let hours = (end.timestamp() - start.timestamp()) / 3600;
+    u32::try_from(hours.max(1)).unwrap_or(u32::MAX)
+}
+
+static DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+static DEFAULT_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+/// Time between two reads of a followed log
+static FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Duration from the command line, else from the config file, else the default
+fn duration_setting(
+    cli_value: Option<Duration>,
+    config_value: &Option<String>,
+    default: Duration,
+) -> Result<Duration> {
+    match (cli_value, config_value) {
+        (Some(d), _) => Ok(d),
+        (None, Some(s)) => parser::parse_duration(s).map_err(Report::msg),
+        (None, None) => Ok(default),
+    }
+}
+
 pub struct App<'a> {
     pub cli: Cli,
+    pub worker: Worker,
     pub display_state: DisplayState<'a>,
-    pub cached_display: Option<DisplayState<'a>>,
+    /// Views left for the one shown, with their highlight, shown again in
+    /// turn by quitting
+    pub cached_displays: Vec<(DisplayState<'a>, Option<usize>)>,
     pub highlighted: Option<usize>,
-    pub cached_highlight: Option<usize>,
     pub popup: Option<MyPopup>,
+    pub prompt: Option<Prompt<'a>>,
+    pub confirm: Option<Confirm>,
+    pub menu: Option<ActionMenu>,
+    /// Job to highlight as soon as it shows in the list
+    pub highlight_job: Option<String>,
     pub fetch_time: FetchTime,
+    pub pending_jobs: Option<u64>,
+    pub pending_view: Option<Pending>,
+    pub tick: usize,
+    pub refresh_interval: Duration,
+    pub min_refresh_interval: Duration,
+    pub last_fetch: Option<Instant>,
+    /// Rows of the last sacct call, filtered again when other jobs are shown
+    pub job_rows: Vec<JobFields>,
+    /// Request for a page of the open log, or for what was appended to it
+    pub pending_page: Option<u64>,
+    /// Request for the next match of the search in the open log
+    pub pending_search: Option<u64>,
+    pub last_tail: Option<Instant>,
+    /// Colours of the lines of the logs opened
+    pub log_highlighter: LogHighlighter,
+}
+
+/// Request sent to the worker whose answer changes the view
+#[derive(Clone, Copy)]
+pub struct Pending {
+    pub id: u64,
+    pub text: &'static str,
 }
 
 impl<'a> App<'a> {
-    pub fn new(cli: Cli) -> App<'a> {
-        App {
+    pub fn new(cli: Cli, config: Config) -> Result<App<'a>> {
+        let source = job_source::from_cli(&cli, &config)?;
+        let log_highlighter = LogHighlighter::new(&config.logs)?;
+        let min_refresh_interval = duration_setting(
+            cli.min_refresh_interval,
+            &config.min_refresh_interval,
+            DEFAULT_MIN_REFRESH_INTERVAL,
+        )?;
+        let refresh_interval = duration_setting(
+            cli.refresh_interval,
+            &config.refresh_interval,
+            DEFAULT_REFRESH_INTERVAL,
+        )?
+        .max(min_refresh_interval);
+        let fetch_time = match cli.since {
+            Some(start) => {
+                parser::check_time_window(start, cli.until).map_err(Report::msg)?;
+                SpecificWindow {
+                    start,
+                    end: cli.until,
+                }
+            }
+            None => Today,
+        };
+        Ok(App {
             cli,
-            cached_display: None,
+            worker: Worker::new(source)?,
+            cached_displays: vec![],
             highlighted: None,
-            cached_highlight: None,
             popup: None,
+            prompt: None,
+            confirm: None,
+            menu: None,
+            highlight_job: None,
             display_state: DisplayState::Empty,
-            fetch_time: Today,
-        }
+            fetch_time,
+            pending_jobs: None,
+            pending_view: None,
+            tick: 0,
+            refresh_interval,
+            min_refresh_interval,
+            last_fetch: None,
+            job_rows: vec![],
+            pending_page: None,
+            pending_search: None,
+            last_tail: None,
+            log_highlighter,
+        })
     }
 
     fn send_enter(&mut self) -> Result<bool> {
@@ -83,39 +313,344 @@ impl<'a> App<'a> {
             DisplayState::Jobs(_) => self.send_char('l'),
             DisplayState::Logs(_) => self.send_char('v'),
             DisplayState::Editor(_) => Ok(false),
+            DisplayState::Details(_) => Ok(false),
+            DisplayState::Scripts(_) => self.send_char('v'),
+            DisplayState::Submit(_) => self.send_char('e'),
         }
     }
 
-    pub fn fetch_jobs(&mut self) -> Result<()> {
+    pub fn fetch_jobs(&mut self) {
         // Only fetch results if needed
+        let wanted = match self.display_state {
+            // a change of query cancels the running fetch
+            DisplayState::Jobs(ref j_info) => {
+                j_info.changed | (j_info.refresh & self.time_to_refresh().is_zero())
+            }
+            DisplayState::Empty => self.pending_jobs.is_none(),
+            _ => false,
+        };
+        // never call sacct more often than the minimum interval
+        if !wanted | !self.time_to_next_fetch().is_zero() {
+            return;
+        }
+        if let DisplayState::Jobs(ref mut j_info) = self.display_state {
+            j_info.changed = false;
+        }
+        self.last_fetch = Some(Instant::now());
+        let window = job_handler::time_window(self.fetch_time);
+        self.pending_jobs = Some(self.worker.send(Request::Jobs { window }));
+    }
+
+    fn since_last_fetch(&self) -> Duration {
+        self.last_fetch.map_or(Duration::MAX, |t| t.elapsed())
+    }
+
+    /// Time left before sacct may be called again
+    pub fn time_to_next_fetch(&self) -> Duration {
+        self.min_refresh_interval
+            .saturating_sub(self.since_last_fetch())
+    }
+
+    /// Time left before the next automatic refresh, which waits for the previous one
+    pub fn time_to_refresh(&self) -> Duration {
+        if self.pending_jobs.is_some() {
+            return self.refresh_interval;
+        }
+        self.refresh_interval
+            .saturating_sub(self.since_last_fetch())
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.pending_jobs.is_some() | self.pending_view.is_some()
+    }
+
+    /// Applies the responses of the worker that arrived since the last call
+    pub fn receive(&mut self) {
+        self.tick = self.tick.wrapping_add(1);
+        while let Some((id, response)) = self.worker.try_recv() {
+            let res = match response {
+                Response::Jobs(res) if self.pending_jobs == Some(id) => {
+                    self.pending_jobs = None;
+                    res.map(|all_job_fields| self.receive_jobs(all_job_fields))
+                }
+                Response::Logs(res) if self.is_pending_view(id) => {
+                    self.pending_view = None;
+                    res.map(|logs| self.receive_logs(logs))
+                }
+                Response::ReadLog { path, page } if self.is_pending_view(id) => {
+                    self.pending_view = None;
+                    page.map(|page| self.receive_log_text(&path, page))
+                }
+                Response::LogPage { path, range, page } if self.pending_page == Some(id) => {
+                    self.pending_page = None;
+                    self.receive_log_page(&path, range, page)
+                }
+                Response::SearchLog { path, found } if self.pending_search == Some(id) => {
+                    self.pending_search = None;
+                    self.receive_log_search(&path, found)
+                }
+                Response::Details(res) if self.is_pending_view(id) => {
+                    self.pending_view = None;
+                    res.map(|details| self.receive_details(details))
+                }
+                Response::Scripts(res) if self.is_pending_view(id) => {
+                    self.pending_view = None;
+                    res.map(|scripts| self.receive_scripts(scripts))
+                }
+                Response::ReadScript { path, script } if self.is_pending_view(id) => {
+                    self.pending_view = None;
+                    script.map(|script| self.receive_script_text(&path, &script))
+                }
+                Response::Action(res) => res.map(|message| self.receive_action_message(message)),
+                // answer to a request that was cancelled
+                _ => Ok(()),
+            };
+            if let Err(e) = res {
+                info!("worker error: {:?}", e);
+                self.popup = Some(MyPopup {
+                    popup_text: format!("Error: {}", e),
+                });
+            }
+        }
+    }
+
+    fn is_pending_view(&self, id: u64) -> bool {
+        self.pending_view.is_some_and(|p| p.id == id)
+    }
+
+    fn receive_jobs(&mut self, all_job_fields: Vec<JobFields>) {
         let job_info = match self.display_state {
-            DisplayState::Jobs(ref mut j_info) => {
-                if j_info.refresh | j_info.changed {
-                    j_info.changed = false;
-                    j_info.clone()
+            DisplayState::Jobs(ref j_info) => j_info.clone(),
+            _ => JobQueryInfo::default(self),
+        };
+        let steps = job_handler::job_steps(&all_job_fields);
+        self.job_rows = all_job_fields.clone();
+        let job_results = job_handler::filter_jobs(self, &job_info, all_job_fields);
+        match self.display_state {
+            DisplayState::Jobs(_) | DisplayState::Empty => {
+                self.highlighted = if job_results.len() >= 2 {
+                    Some(1)
                 } else {
-                    return Ok(());
+                    None
+                };
+                self.update_job_display(job_results, steps);
+                self.highlight_wanted_job();
+            }
+            _ => (),
+        }
+    }
+
+    fn receive_logs(&mut self, logs: Vec<String>) {
+        if !matches!(self.display_state, DisplayState::Jobs(_)) {
+            return;
+        }
+        if logs.is_empty() {
+            self.popup = Some(MyPopup {
+                popup_text: "No log file found.".to_string(),
+            })
+        } else {
+            self.push_display(DisplayState::Logs(logs), Some(0));
+        }
+    }
+
+    fn receive_log_text(&mut self, path: &str, page: LogPage) {
+        if let DisplayState::Logs(_) = self.display_state {
+            let editor = Editor::new(path, page, self.log_highlighter.clone());
+            self.push_display(DisplayState::Editor(Box::new(editor)), self.highlighted);
+        }
+    }
+
+    /// Shows the view, the one it replaces being shown again once it is quit
+    fn push_display(&mut self, display: DisplayState<'a>, highlighted: Option<usize>) {
+        let previous = std::mem::replace(&mut self.display_state, display);
+        self.cached_displays.push((previous, self.highlighted));
+        self.highlighted = highlighted;
+    }
+
+    /// Asks for the next match of a search past the loaded lines, for the page
+    /// of the log the viewer needs, or for what was appended to the followed
+    /// log once per FOLLOW_INTERVAL
+    pub fn fetch_log_page(&mut self) {
+        let DisplayState::Editor(ref mut editor) = self.display_state else {
+            return;
+        };
+        if let Some(search) = editor.file_search.take() {
+            let request = Request::SearchLog {
+                path: editor.path.clone(),
+                search,
+            };
+            self.pending_search = Some(self.worker.send(request));
+        }
+        if self.pending_page.is_some() {
+            return;
+        }
+        let range = match editor.next_page() {
+            Some(range) => range,
+            None => {
+                let waiting = self
+                    .last_tail
+                    .is_some_and(|t| t.elapsed() < FOLLOW_INTERVAL);
+                // reading on would load pages away from the paused view
+                if !editor.follow || waiting || editor.end < editor.file_size {
+                    return;
                 }
+                self.last_tail = Some(Instant::now());
+                PageRange::After(editor.end)
             }
-            DisplayState::Empty => JobQueryInfo::default(self),
-            _ => return Ok(()),
         };
-        let job_results = job_handler::fetch_jobs(self, job_info)?;
-        self.highlighted = if job_results.len() >= 2 {
-            Some(1)
-        } else {
-            None
+        let request = Request::LogPage {
+            path: editor.path.clone(),
+            range,
         };
-        self.update_job_display(job_results);
-        Ok(())
+        self.pending_page = Some(self.worker.send(request));
     }
 
-    fn update_job_display(&mut self, new_results: Vec<JobFields>) {
+    fn receive_log_page(
+        &mut self,
+        path: &str,
+        range: PageRange,
+        page: Result<LogPage>,
+    ) -> Result<()> {
+        let DisplayState::Editor(ref mut editor) = self.display_state else {
+            return Ok(());
+        };
+        // the file may have been closed, or reopened, since the request
+        if editor.path != path || !editor.fits(range) {
+            return Ok(());
+        }
+        match page {
+            Result::Ok(page) => {
+                editor.add_page(range, page);
+                Ok(())
+            }
+            Err(e) => {
+                // stops reading instead of failing again every second
+                editor.follow = false;
+                editor.failed = true;
+                editor.cancel_file_search();
+                Err(e)
+            }
+        }
+    }
+
+    fn receive_log_search(&mut self, path: &str, found: Result<Option<u64>>) -> Result<()> {
+        let DisplayState::Editor(ref mut editor) = self.display_state else {
+            return Ok(());
+        };
+        if editor.path != path {
+            return Ok(());
+        }
+        match found {
+            Result::Ok(found) => {
+                editor.show_file_match(found);
+                Ok(())
+            }
+            Err(e) => {
+                editor.cancel_file_search();
+                Err(e)
+            }
+        }
+    }
+
+    /// Shows the jobs of the time from the rows of the last sacct call, which
+    /// is only called when there are none yet
+    fn show_job_time(&mut self, time: JobTime) {
+        let DisplayState::Jobs(ref mut job_info) = self.display_state else {
+            return;
+        };
+        job_info.time = time;
+        if self.job_rows.is_empty() {
+            job_info.changed = true;
+            return;
+        }
+        let job_info = job_info.clone();
+        let job_results = job_handler::filter_jobs(self, &job_info, self.job_rows.clone());
+        if let DisplayState::Jobs(ref mut job_info) = self.display_state {
+            job_info.job_list = job_results;
+            job_info.make_display();
+            let shown = job_info.job_display.len();
+            self.highlighted = match shown {
+                0 | 1 => None,
+                _ => Some(self.highlighted.unwrap_or(1).clamp(1, shown - 1)),
+            };
+        }
+    }
+
+    fn receive_details(&mut self, details: JobDetails) {
+        if let DisplayState::Jobs(_) = self.display_state {
+            self.push_display(DisplayState::Details(details), Some(0));
+        }
+    }
+
+    fn receive_scripts(&mut self, scripts: Vec<String>) {
+        if !matches!(self.display_state, DisplayState::Jobs(_)) {
+            return;
+        }
+        if scripts.is_empty() {
+            self.popup = Some(MyPopup {
+                popup_text: "No batch script found.".to_string(),
+            })
+        } else {
+            self.push_display(DisplayState::Scripts(scripts), Some(0));
+        }
+    }
+
+    fn receive_script_text(&mut self, path: &str, script: &str) {
+        if let DisplayState::Scripts(_) = self.display_state {
+            let form = SubmitForm::new(path, script);
+            let highlighted = if form.directives.is_empty() {
+                None
+            } else {
+                Some(0)
+            };
+            self.push_display(DisplayState::Submit(form), highlighted);
+        }
+    }
+
+    fn highlight_wanted_job(&mut self) {
+        if let (Some(job_id), DisplayState::Jobs(ref job_info)) =
+            (&self.highlight_job, &self.display_state)
+        {
+            let position = job_info.job_display.iter().position(|job_fields| {
+                job_fields
+                    .id()
+                    .is_some_and(|id| id.target().to_string() == *job_id)
+            });
+            if position.is_some() {
+                self.highlighted = position;
+                self.highlight_job = None;
+            }
+        }
+    }
+
+    fn receive_action_message(&mut self, message: String) {
+        if let Some(job_id) = job_action::submitted_job_id(&message) {
+            self.highlight_job = Some(job_id);
+            // back to the jobs, where the new one shows once listed
+            if let DisplayState::Submit(_) = self.display_state {
+                self.display_state = DisplayState::Empty;
+                while let Some((display, highlighted)) = self.cached_displays.pop() {
+                    self.display_state = display;
+                    self.highlighted = highlighted;
+                }
+            }
+        }
+        self.popup = Some(MyPopup {
+            popup_text: message,
+        });
+        // show the new state of the job
+        if let DisplayState::Jobs(ref mut job_info) = self.display_state {
+            job_info.changed = true;
+        }
+    }
+
+    fn update_job_display(&mut self, new_results: Vec<JobFields>, steps: Vec<JobFields>) {
         if let DisplayState::Jobs(ref mut job_info) = self.display_state {
             job_info.job_list = new_results;
+            job_info.steps = steps;
             job_info.make_display()
         } else {
-            let mut jqi = JobQueryInfo::from_result(new_results, self);
+            let mut jqi = JobQueryInfo::from_result(new_results, steps, self);
             jqi.make_display();
             self.display_state = DisplayState::Jobs(jqi);
         }
@@ -124,6 +659,42 @@ impl<'a> App<'a> {
         if self.popup.is_some() {
             self.popup = None;
             Ok(false)
+        } else if let Some(confirm) = self.confirm.take() {
+            let action = match keycode {
+                KeyCode::Char('y') | KeyCode::Enter => Some(confirm.action),
+                KeyCode::Char('p') => confirm.pending_action,
+                _ => None,
+            };
+            if let Some(action) = action {
+                info!("running {:?}", action);
+                self.worker.send(Request::Action(action));
+            }
+            Ok(false)
+        } else if let Some(menu) = self.menu.take() {
+            let chosen = menu
+                .commands
+                .iter()
+                .find(|command| keycode == KeyCode::Char(command.key()));
+            if let Some(&command) = chosen {
+                let action = JobAction::Scontrol {
+                    command,
+                    job_id: menu.job_id,
+                };
+                info!("running {:?}", action);
+                self.worker.send(Request::Action(action));
+            }
+            Ok(false)
+        } else if let Some(ref mut prompt) = self.prompt {
+            match prompt.send_keycode(keycode) {
+                PromptResult::Editing => (),
+                PromptResult::Cancelled => self.prompt = None,
+                PromptResult::Submitted(text) => {
+                    let action = prompt.action.clone();
+                    self.prompt = None;
+                    self.submit_prompt(action, &text);
+                }
+            }
+            Ok(false)
         } else {
             match keycode {
                 KeyCode::Char(c) => self.send_char(c),
@@ -141,18 +712,101 @@ impl<'a> App<'a> {
         }
     }
 
+    fn submit_prompt(&mut self, action: PromptAction, text: &str) {
+        match action {
+            PromptAction::TimeWindow => match FetchTime::from_range(text) {
+                Result::Ok(fetch_time) => {
+                    self.fetch_time = fetch_time;
+                    if let DisplayState::Jobs(ref mut job_info) = self.display_state {
+                        job_info.changed = true;
+                    }
+                }
+                Err(e) => {
+                    self.popup = Some(MyPopup {
+                        popup_text: e.to_string(),
+                    });
+                }
+            },
+            PromptAction::Signal { job_id, array } => {
+                let signal = text.trim();
+                if !signal.is_empty() {
+                    let action = JobAction::Cancel {
+                        job_id,
+                        signal: Some(signal.to_string()),
+                        pending_only: false,
+                    };
+                    self.confirm = Some(Confirm::new(action, array));
+                }
+            }
+            PromptAction::Resubmit { workdir } => {
+                let command_line = text.trim();
+                if !command_line.is_empty() {
+                    let action = JobAction::Submit {
+                        workdir,
+                        command_line: command_line.to_string(),
+                    };
+                    info!("running {:?}", action);
+                    self.worker.send(Request::Action(action));
+                }
+            }
+            PromptAction::Scripts => {
+                let dir = match text.trim() {
+                    "" => ".",
+                    dir => dir,
+                };
+                let request = Request::Scripts {
+                    dir: dir.to_string(),
+                };
+                self.pending_view = Some(Pending {
+                    id: self.worker.send(request),
+                    text: "scripts",
+                });
+            }
+            PromptAction::Directive { index } => match Directive::parse(text) {
+                Some(mut directive) => {
+                    directive.overridden = true;
+                    if let DisplayState::Submit(ref mut form) = self.display_state {
+                        match index {
+                            Some(i) => form.directives[i] = directive,
+                            None => {
+                                form.directives.push(directive);
+                                self.highlighted = Some(form.directives.len() - 1);
+                            }
+                        }
+                    }
+                }
+                None => {
+                    self.popup = Some(MyPopup {
+                        popup_text: format!("Not an sbatch option: {}", text),
+                    });
+                }
+            },
+            PromptAction::Search { backward } => {
+                if let DisplayState::Editor(ref mut editor) = self.display_state {
+                    if let Err(e) = editor.search(text, backward) {
+                        self.popup = Some(MyPopup { popup_text: e });
+                    }
+                }
+            }
+        }
+    }
+
     fn get_highlighted_i(&self) -> Result<usize> {
         self.highlighted.ok_or(Report::msg("No highlights"))
     }
 
     fn send_quit(&mut self) -> bool {
+        // leaving the view cancels what was loading for it
+        self.pending_view = None;
         match self.display_state {
-            DisplayState::Logs(_) | DisplayState::Editor(_) => {
-                if let Some(cached) = self.cached_display.take() {
-                    self.highlighted = self.cached_highlight;
-                    self.cached_highlight = None;
+            DisplayState::Logs(_)
+            | DisplayState::Editor(_)
+            | DisplayState::Details(_)
+            | DisplayState::Scripts(_)
+            | DisplayState::Submit(_) => {
+                if let Some((cached, highlighted)) = self.cached_displays.pop() {
                     self.display_state = cached;
-                    self.cached_display = None;
+                    self.highlighted = highlighted;
                 } else {
                     self.display_state = DisplayState::Empty;
                 }
@@ -169,11 +823,19 @@ impl<'a> App<'a> {
                 let num_results = job_info.job_display.len();
                 self.offset_highlighted_with_params(offset, num_results, num_skip_line);
             }
-            DisplayState::Logs(ref strings) => {
+            DisplayState::Logs(ref strings) | DisplayState::Scripts(ref strings) => {
                 let num_results = strings.len();
                 let num_skip_line = 0;
                 self.offset_highlighted_with_params(offset, num_results, num_skip_line);
             }
+            DisplayState::Details(ref details) => {
+                let num_results = details.len();
+                self.offset_highlighted_with_params(offset, num_results, 0);
+            }
+            DisplayState::Submit(ref form) => {
+                let num_results = form.directives.len();
+                self.offset_highlighted_with_params(offset, num_results, 0);
+            }
             DisplayState::Editor(_) => panic!("Cannot offset in editor mode"),
             DisplayState::Empty => (),
         }
@@ -205,27 +867,43 @@ impl<'a> App<'a> {
 }
 
 pub static DESCRIPTION_JOB: &str =
-    "[q]uit [t]oggle_refresh [l]ogs [f]inished [r]unning [a]ll [s]eff [o]lder [n]ewer";
+    "[q]uit [e]xpand [F]ilter_tasks [y]ank_tasks [t]oggle_refresh [R]efresh_now [l]ogs [f]inished [r]unning [a]ll [s]eff [o]lder [n]ewer [w]indow [c]ancel [C]ancel_signal [x]actions [b]resubmit [S]ubmit [d]etails";
 pub static DESCRIPTION_LOG: &str = "[q]uit [v]iew";
+pub static DESCRIPTION_EDITOR: &str =
+    "[q]uit [f]ollow [/?]search [n/N]ext [i]gnore_case [[ ]]highlight [a]nsi";
+pub static DESCRIPTION_DETAILS: &str = "[q]uit";
+pub static DESCRIPTION_SCRIPTS: &str = "[q]uit [v]iew";
+pub static DESCRIPTION_SUBMIT: &str = "[q]uit [e]dit [a]dd [s]ubmit";
 
 impl<'a> App<'a> {
     fn send_char(&mut self, c_sent: char) -> Result<bool> {
         let res_highlighted_i = self.get_highlighted_i();
         match (c_sent, &mut self.display_state) {
             ('q', _) => return Ok(self.send_quit()),
+            ('/' | '?', DisplayState::Editor(_)) => {
+                let action = PromptAction::Search {
+                    backward: c_sent == '?',
+                };
+                let title = match c_sent {
+                    '?' => "Search up",
+                    _ => "Search",
+                };
+                self.prompt = Some(
+                    Prompt::new(title, "", action)
+                        .with_placeholder("regex, empty for the last search"),
+                );
+            }
             (_, DisplayState::Editor(ref mut editor)) => editor.send_char(c_sent),
             (_, DisplayState::Empty) => (),
             ('l', DisplayState::Jobs(ref mut job_info)) => {
                 let job_fields = &job_info.job_display[res_highlighted_i?];
-                let logs = job_parser::fetch_logs(self.cli.run_mode, job_fields)?;
-                if logs.is_empty() {
-                    self.popup = Some(MyPopup {
-                        popup_text: "No log file found.".to_string(),
-                    })
-                } else {
-                    self.highlighted = Some(0);
-                    self.display_state = DisplayState::Logs(logs);
-                }
+                let request = Request::Logs {
+                    job: Box::new(job_fields.clone()),
+                };
+                self.pending_view = Some(Pending {
+                    id: self.worker.send(request),
+                    text: "logs",
+                });
             }
             ('o', DisplayState::Jobs(ref mut job_info)) => {
                 self.fetch_time = self.fetch_time.older();
@@ -235,27 +913,172 @@ impl<'a> App<'a> {
                 self.fetch_time = self.fetch_time.newer();
                 job_info.changed = true;
             }
-            ('t', DisplayState::Jobs(ref mut job_info)) => job_info.refresh = !job_info.refresh,
-            ('f', DisplayState::Jobs(ref mut job_info)) => {
-                job_info.time = JobTime::Finished;
-                job_info.changed = true;
+            ('w', DisplayState::Jobs(_)) => {
+                self.prompt = Some(
+                    Prompt::new("Time window", "", PromptAction::TimeWindow)
+                        .with_placeholder("e.g. 2w, yesterday..today, 2024-03-01..2024-03-05"),
+                );
             }
-            ('r', DisplayState::Jobs(ref mut job_info)) => {
-                job_info.time = JobTime::Running;
-                job_info.changed = true;
+            ('c', DisplayState::Jobs(ref mut job_info)) => {
+                match action_target(&job_info.job_display[res_highlighted_i?]) {
+                    Result::Ok(id) => {
+                        let action = JobAction::Cancel {
+                            job_id: id.target().to_string(),
+                            signal: None,
+                            pending_only: false,
+                        };
+                        self.confirm = Some(Confirm::new(action, id.is_task_range()));
+                    }
+                    Err(popup_text) => self.popup = Some(MyPopup { popup_text }),
+                }
+            }
+            ('C', DisplayState::Jobs(ref mut job_info)) => {
+                match action_target(&job_info.job_display[res_highlighted_i?]) {
+                    Result::Ok(id) => {
+                        let action = PromptAction::Signal {
+                            job_id: id.target().to_string(),
+                            array: id.is_task_range(),
+                        };
+                        self.prompt = Some(
+                            Prompt::new("Signal", "", action)
+                                .with_placeholder("e.g. USR1, TERM, KILL"),
+                        );
+                    }
+                    Err(popup_text) => self.popup = Some(MyPopup { popup_text }),
+                }
+            }
+            ('b', DisplayState::Jobs(ref mut job_info)) => {
+                let mut job_fields = &job_info.job_display[res_highlighted_i?];
+                // a step has no submit line of its own, resubmit its job
+                if let Some(id) = job_fields.id().filter(|id| id.is_step()) {
+                    let job_id = id.job().to_string();
+                    if let Some(job) = job_info.job_list.iter().find(|job| job.job_id == job_id) {
+                        job_fields = job;
+                    }
+                }
+                if job_fields.submit_line.is_empty() {
+                    self.popup = Some(MyPopup {
+                        popup_text: "No submit line recorded for this job.".to_string(),
+                    });
+                } else {
+                    let title = format!("Resubmit from {}", job_fields.workdir);
+                    let action = PromptAction::Resubmit {
+                        workdir: job_fields.workdir.clone(),
+                    };
+                    self.prompt = Some(Prompt::new(&title, &job_fields.submit_line, action));
+                }
+            }
+            ('d', DisplayState::Jobs(ref mut job_info)) => {
+                let job_fields = &job_info.job_display[res_highlighted_i?];
+                let job_id = match job_fields.id() {
+                    Some(id) => id.target().to_string(),
+                    None => job_fields.job_id.clone(),
+                };
+                let request = Request::Details { job_id };
+                self.pending_view = Some(Pending {
+                    id: self.worker.send(request),
+                    text: "details",
+                });
+            }
+            ('S', DisplayState::Jobs(ref mut job_info)) => {
+                let workdir = match res_highlighted_i {
+                    Result::Ok(i) => job_info.job_display[i].workdir.clone(),
+                    Err(_) => String::new(),
+                };
+                self.prompt = Some(
+                    Prompt::new("Find batch scripts in", &workdir, PromptAction::Scripts)
+                        .with_placeholder("directory, empty for the current one"),
+                );
+            }
+            ('x', DisplayState::Jobs(ref mut job_info)) => {
+                let job_fields = &job_info.job_display[res_highlighted_i?];
+                let commands: Vec<_> = ScontrolCommand::all()
+                    .into_iter()
+                    .filter(|command| command.allowed(job_fields))
+                    .collect();
+                match action_target(job_fields) {
+                    Err(popup_text) => self.popup = Some(MyPopup { popup_text }),
+                    Result::Ok(_) if commands.is_empty() => {
+                        self.popup = Some(MyPopup {
+                            popup_text: format!(
+                                "No action for a job in state {}",
+                                job_fields.state
+                            ),
+                        });
+                    }
+                    Result::Ok(id) => {
+                        self.menu = Some(ActionMenu {
+                            job_id: id.target().to_string(),
+                            commands,
+                        });
+                    }
+                }
             }
+            ('e', DisplayState::Jobs(ref mut job_info)) => {
+                self.highlighted = Some(job_info.toggle_fold(res_highlighted_i?));
+            }
+            ('F', DisplayState::Jobs(ref mut job_info)) => {
+                job_info.task_filter = job_info.task_filter.next();
+                job_info.make_display();
+                let last = job_info.job_display.len() - 1;
+                self.highlighted = self.highlighted.map(|i| i.min(last));
+            }
+            ('y', DisplayState::Jobs(ref mut job_info)) => {
+                let popup_text = match job_info.array_spec(res_highlighted_i?) {
+                    Some(spec) => {
+                        ui::copy_to_clipboard(&spec)?;
+                        format!("Copied {}", spec)
+                    }
+                    None => "No array task to copy.".to_string(),
+                };
+                self.popup = Some(MyPopup { popup_text });
+            }
+            ('t', DisplayState::Jobs(ref mut job_info)) => job_info.refresh = !job_info.refresh,
+            ('R', DisplayState::Jobs(ref mut job_info)) => job_info.changed = true,
+            ('f', DisplayState::Jobs(_)) => self.show_job_time(JobTime::Finished),
+            ('r', DisplayState::Jobs(_)) => self.show_job_time(JobTime::Running),
             ('s', DisplayState::Jobs(ref mut job_info)) => {
                 job_info.efficiency_display = true;
             }
-            ('a', DisplayState::Jobs(ref mut job_info)) => {
-                job_info.time = JobTime::All;
-                job_info.changed = true;
-            }
+            ('a', DisplayState::Jobs(_)) => self.show_job_time(JobTime::All),
             ('v', DisplayState::Logs(logs)) => {
-                self.cached_display = Some(DisplayState::Logs(logs.clone()));
-                self.cached_highlight = self.highlighted;
-                let logs = job_handler::read_file(self.cli.run_mode, &logs[res_highlighted_i?])?;
-                self.display_state = DisplayState::Editor(Editor::new(&logs));
+                let request = Request::ReadLog {
+                    path: logs[res_highlighted_i?].clone(),
+                };
+                self.pending_view = Some(Pending {
+                    id: self.worker.send(request),
+                    text: "log file",
+                });
+            }
+            ('v', DisplayState::Scripts(scripts)) => {
+                let request = Request::ReadScript {
+                    path: scripts[res_highlighted_i?].clone(),
+                };
+                self.pending_view = Some(Pending {
+                    id: self.worker.send(request),
+                    text: "script",
+                });
+            }
+            ('e', DisplayState::Submit(form)) if res_highlighted_i.is_ok() => {
+                let i = res_highlighted_i?;
+                let text = form.directives[i].display();
+                let action = PromptAction::Directive { index: Some(i) };
+                self.prompt = Some(Prompt::new("Directive", &text, action));
+            }
+            // a script without directives has none to edit, so one is added
+            ('e' | 'a', DisplayState::Submit(_)) => {
+                let action = PromptAction::Directive { index: None };
+                self.prompt = Some(
+                    Prompt::new("New directive", "", action).with_placeholder("e.g. --mem=8G"),
+                );
+            }
+            ('s', DisplayState::Submit(form)) => {
+                let action = JobAction::Submit {
+                    workdir: form.workdir(),
+                    command_line: form.command_line(),
+                };
+                info!("running {:?}", action);
+                self.worker.send(Request::Action(action));
             }
             // ('j' | 'k', DisplayState::Report(_)) => {}
             ('j', _) => self.increase_highlighted()?,
@@ -265,3 +1088,98 @@ impl<'a> App<'a> {
         Ok(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::fixture::DEFAULT_FIXTURE_DIR;
+
+    #[test]
+    fn job_actions_refuse_steps() {
+        let sacct = std::fs::read_to_string(format!("{}/sacct.txt", DEFAULT_FIXTURE_DIR)).unwrap();
+        let jobs = JobFields::from_sacct_str(&sacct).unwrap();
+        let target = |job_id: &str| {
+            let job = jobs.iter().find(|job| job.job_id == job_id).unwrap();
+            action_target(job).map(|id| id.target().to_string())
+        };
+        assert_eq!(target("1001").unwrap(), "1001");
+        assert_eq!(target("1003_2").unwrap(), "1003_2");
+        assert_eq!(target("1003_[6-8]").unwrap(), "1003");
+        assert!(target("1001.batch").unwrap_err().contains("1001"));
+        assert!(target("1008+1.batch").is_err());
+    }
+
+    #[test]
+    fn older_goes_past_a_week() {
+        let mut fetch_time = Today;
+        let mut descriptions = vec![];
+        for _ in 0..6 {
+            fetch_time = fetch_time.older();
+            descriptions.push(fetch_time.description());
+        }
+        assert_eq!(
+            descriptions,
+            [
+                "last 3 days",
+                "last week",
+                "last 2 weeks",
+                "last month",
+                "last 3 months",
+                "last 3 months"
+            ]
+        );
+        assert_eq!(ThreeMonthsAgo.newer(), AMonthAgo);
+        assert_eq!(ThreeDaysAgo.newer().newer(), Today);
+    }
+
+    #[test]
+    fn specific_windows_shift_by_their_span() {
+        let window = FetchTime::from_range("3d..1d").unwrap();
+        let older = SpecificWindow {
+            start: TimeBound::HoursAgo(120),
+            end: Some(TimeBound::HoursAgo(72)),
+        };
+        assert_eq!(window.older(), older);
+        assert_eq!(older.newer(), window);
+        // a window is not moved past now
+        assert_eq!(
+            window.newer(),
+            SpecificWindow {
+                start: TimeBound::HoursAgo(24),
+                end: None
+            }
+        );
+        let open = FetchTime::from_range("2d").unwrap();
+        assert_eq!(open.newer(), open);
+        assert_eq!(
+            open.older(),
+            SpecificWindow {
+                start: TimeBound::HoursAgo(96),
+                end: Some(TimeBound::HoursAgo(48)),
+            }
+        );
+        let dates = FetchTime::from_range("2024-03-03..2024-03-05").unwrap();
+        assert_eq!(
+            dates.older(),
+            FetchTime::from_range("2024-03-01..2024-03-03").unwrap()
+        );
+        assert_eq!(
+            dates.newer(),
+            FetchTime::from_range("2024-03-05..2024-03-07").unwrap()
+        );
+    }
+
+    #[test]
+    fn time_window_prompt() {
+        assert_eq!(FetchTime::from_range(" ").unwrap(), Today);
+        assert_eq!(
+            FetchTime::from_range("1w..").unwrap(),
+            SpecificWindow {
+                start: TimeBound::HoursAgo(168),
+                end: None
+            }
+        );
+        assert!(FetchTime::from_range("1d..1w").is_err());
+        assert!(FetchTime::from_range("soon").is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use color_eyre::eyre::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Time between two refreshes, e.g. "30s"
+    pub refresh_interval: Option<String>,
+    /// Minimum time between two calls to sacct
+    pub min_refresh_interval: Option<String>,
+    pub ssh: SshConfig,
+    pub rest: RestConfig,
+    pub logs: LogConfig,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SshConfig {
+    pub host: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_file: Option<PathBuf>,
+    pub proxy_jump: Option<String>,
+    pub options: Vec<String>,
+    pub control_master: Option<bool>,
+    pub control_persist: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RestConfig {
+    pub url: Option<String>,
+    pub api_version: Option<String>,
+    pub user: Option<String>,
+    pub token: Option<String>,
+    pub token_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Log paths with the `%` placeholders of sbatch, relative to the workdir
+    pub templates: Vec<String>,
+    /// Depth of the search for files named after the job, in the workdir
+    pub find_depth: Option<usize>,
+    /// Colours of the lines matching a regex in the log viewer, tried before
+    /// the default ones
+    pub highlights: Vec<HighlightConfig>,
+    /// Whether tracebacks, errors and warnings are coloured without any rule
+    /// being configured (default: true)
+    pub default_highlights: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HighlightConfig {
+    pub pattern: String,
+    /// A colour name like "red" or "lightyellow", an index or "#rrggbb"
+    pub color: String,
+}
+
+impl Config {
+    /// Loads the config from `path`, or from the default location if it exists.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let path = match path {
+            Some(p) => p.to_path_buf(),
+            None => match default_path() {
+                Some(p) if p.exists() => p,
+                _ => return Ok(Config::default()),
+            },
+        };
+        let content = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Could not read config file {}", path.display()))?;
+        toml::from_str(&content)
+            .wrap_err_with(|| format!("Could not parse config file {}", path.display()))
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("sboard").join("config.toml"))
+}
@@ -1,16 +1,599 @@
+use crate::ansi::{self, AnsiMode};
+use crate::jobs::log_page::{FileSearch, LogPage, PageRange, PAGE_SIZE};
+use crate::log_highlight::LogHighlighter;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use regex::Regex;
+use std::cell::Cell;
+use std::ops::Range;
+use tui_textarea::CursorMove;
 use tui_textarea::TextArea;
-use tui_textarea::{CursorMove, Scrolling};
 
+/// How letters of the search pattern match
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaseMode {
+    /// Ignores case unless the pattern has an uppercase letter, like vim's smartcase
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+impl CaseMode {
+    fn next(self) -> Self {
+        match self {
+            CaseMode::Smart => CaseMode::Sensitive,
+            CaseMode::Sensitive => CaseMode::Insensitive,
+            CaseMode::Insensitive => CaseMode::Smart,
+        }
+    }
+
+    fn ignores_case(self, pattern: &str) -> bool {
+        match self {
+            CaseMode::Smart => !has_uppercase(pattern),
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CaseMode::Smart => "smart case",
+            CaseMode::Sensitive => "match case",
+            CaseMode::Insensitive => "ignore case",
+        }
+    }
+}
+
+/// Last pattern searched with `/` or `?`
+pub struct Search {
+    pub pattern: String,
+    /// Searched with `?`, which `n` keeps going up
+    pub backward: bool,
+    /// Row and column of the start of each loaded match, in order
+    pub matches: Vec<(usize, usize)>,
+    /// The search of the whole file found no match
+    pub missing: bool,
+}
+
+/// Rows kept loaded around the cursor when lines are dropped
+static KEEP_ROWS: usize = 500;
+/// Distance to the first or last loaded row at which the next page is read
+static PREFETCH_ROWS: usize = 100;
+/// Lines are dropped, away from the cursor, once this many are loaded
+static MAX_WINDOW_BYTES: u64 = 4 * PAGE_SIZE;
+static MAX_WINDOW_LINES: usize = 50_000;
+static TAB_LENGTH: usize = 4;
+
+/// Viewer of a log file, holding the pages read around the cursor only
 pub struct Editor<'a> {
     pub textarea: TextArea<'a>,
+    /// Log file shown, read again from `end` when following it
+    pub path: String,
+    /// Offsets in the file of the lines loaded
+    pub start: u64,
+    pub end: u64,
+    /// Size of the file when last read
+    pub file_size: u64,
+    /// Bytes of each loaded line in the file
+    line_bytes: Vec<u64>,
+    /// Loaded lines as read, the text area holding them as shown
+    raw_lines: Vec<String>,
+    /// Styles given by the escape sequences of each line shown
+    line_styles: Vec<Vec<(Range<usize>, Style)>>,
+    pub ansi_mode: AnsiMode,
+    /// Appends what the job writes to the file, like `tail -f`
+    pub follow: bool,
+    /// Page to read in place of the loaded ones, for `g` and `G`
+    jump: Option<PageRange>,
+    /// A page could not be read, so no other is until the log is opened again
+    pub failed: bool,
+    pub search: Option<Search>,
+    /// Search to run over the file, the next match not being loaded
+    pub file_search: Option<FileSearch>,
+    /// Direction of the search running over the file, until its match is shown
+    searching: Option<bool>,
+    pub case_mode: CaseMode,
+    highlighter: LogHighlighter,
+    /// First row and column in view, and the height of the view, kept from
+    /// one draw to the next
+    view: Cell<(usize, usize)>,
+    height: Cell<usize>,
 }
 
 impl<'a> Editor<'a> {
-    pub fn new(text: &str) -> Self {
-        let vec_lines: Vec<String> = text.split('\n').map(|s| s.to_string()).collect();
-        Editor {
-            textarea: TextArea::new(vec_lines),
+    /// Viewer of the last page of the log, at its end
+    pub fn new(path: &str, page: LogPage, highlighter: LogHighlighter) -> Self {
+        let mut editor = Editor {
+            textarea: TextArea::default(),
+            path: path.to_string(),
+            start: page.start,
+            end: page.end,
+            file_size: page.file_size,
+            line_bytes: page.line_bytes,
+            raw_lines: vec![],
+            line_styles: vec![],
+            ansi_mode: AnsiMode::Render,
+            follow: false,
+            jump: None,
+            failed: false,
+            search: None,
+            file_search: None,
+            searching: None,
+            case_mode: CaseMode::Smart,
+            highlighter,
+            view: Cell::new((0, 0)),
+            height: Cell::new(1),
+        };
+        editor.set_lines(page.lines, (usize::MAX, 0));
+        editor
+    }
+
+    /// Whether the cursor is on the last line, which following keeps it on
+    pub fn at_bottom(&self) -> bool {
+        self.textarea.cursor().0 + 1 >= self.textarea.lines().len()
+    }
+
+    /// Whether the whole file is loaded
+    pub fn is_whole(&self) -> bool {
+        self.start == 0 && self.end >= self.file_size
+    }
+
+    /// Page to read next: the one asked by `g` or `G`, or the one next to the
+    /// loaded lines when the cursor gets close to them
+    pub fn next_page(&mut self) -> Option<PageRange> {
+        if self.failed {
+            return None;
+        }
+        if let Some(range) = self.jump.take() {
+            return Some(range);
+        }
+        let row = self.textarea.cursor().0;
+        if row < PREFETCH_ROWS && self.start > 0 {
+            Some(PageRange::Before(self.start))
+        } else if row + PREFETCH_ROWS >= self.textarea.lines().len() && self.end < self.file_size {
+            Some(PageRange::After(self.end))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the page read for `range` still fits next to the loaded lines
+    pub fn fits(&self, range: PageRange) -> bool {
+        match range {
+            PageRange::Before(offset) => offset == self.start,
+            PageRange::After(offset) => offset == self.end,
+            PageRange::Head | PageRange::Tail | PageRange::At(_) => true,
+        }
+    }
+
+    /// Adds the page read for `range`, dropping lines far from the cursor when
+    /// too many are loaded
+    pub fn add_page(&mut self, range: PageRange, page: LogPage) {
+        self.file_size = page.file_size;
+        match range {
+            PageRange::Head | PageRange::Tail | PageRange::At(_) => {
+                self.start = page.start;
+                self.end = page.end;
+                self.line_bytes = page.line_bytes;
+                let cursor = match range {
+                    PageRange::Tail => (usize::MAX, 0),
+                    _ => (0, 0),
+                };
+                self.view.set((0, 0));
+                self.set_lines(page.lines, cursor);
+                if let Some(backward) = self.searching.take() {
+                    self.jump_to_row_match(0, backward);
+                }
+            }
+            PageRange::Before(_) => self.prepend(page),
+            PageRange::After(_) => self.append(page),
+        }
+    }
+
+    /// Adds the page ending where the loaded lines start, its last line
+    /// continuing the first loaded one
+    fn prepend(&mut self, page: LogPage) {
+        let (row, col) = self.textarea.cursor();
+        let added = page.lines.len() - 1;
+        let mut lines = page.lines;
+        lines[added].push_str(&self.raw_lines[0]);
+        lines.extend_from_slice(&self.raw_lines[1..]);
+        let mut line_bytes = page.line_bytes;
+        line_bytes[added] += self.line_bytes[0];
+        line_bytes.extend_from_slice(&self.line_bytes[1..]);
+        self.line_bytes = line_bytes;
+        self.start = page.start;
+        self.set_lines(lines, (row + added, col));
+        self.shift_view(added as isize);
+        self.trim_bottom();
+    }
+
+    /// Adds the page starting where the loaded lines end, like text written
+    /// to the file since it was read. When following, the view follows the
+    /// end of the file unless the user moved up.
+    fn append(&mut self, page: LogPage) {
+        self.end = page.end;
+        if page.lines.len() == 1 && page.lines[0].is_empty() {
+            return;
+        }
+        let at_bottom = self.at_bottom();
+        let (row, col) = self.textarea.cursor();
+        let last_row = self.textarea.lines().len() - 1;
+        // the last line is shown again, what was appended to it possibly
+        // writing over it
+        self.raw_lines[last_row].push_str(&page.lines[0]);
+        self.raw_lines.extend_from_slice(&page.lines[1..]);
+        let (shown, styles): (Vec<String>, Vec<_>) = self.raw_lines[last_row..]
+            .iter()
+            .map(|line| ansi::parse_line(line, self.ansi_mode))
+            .unzip();
+        self.line_styles.truncate(last_row);
+        self.line_styles.extend(styles);
+        self.textarea.move_cursor(CursorMove::Bottom);
+        self.textarea.move_cursor(CursorMove::Head);
+        self.textarea.delete_line_by_end();
+        self.textarea.insert_str(shown.join("\n"));
+        if self.follow && at_bottom {
+            self.textarea.move_cursor(CursorMove::Head);
+        } else {
+            self.jump_cursor((row, col));
+        }
+        self.line_bytes[last_row] += page.line_bytes[0];
+        self.line_bytes.extend_from_slice(&page.line_bytes[1..]);
+        // the last line may have been completed by the text
+        if let Some(ref mut search) = self.search {
+            search.matches.retain(|&(row, _)| row < last_row);
+        }
+        self.find_matches(last_row);
+        self.trim_top();
+    }
+
+    fn too_large(&self, lines: usize) -> bool {
+        self.end - self.start > MAX_WINDOW_BYTES || lines > MAX_WINDOW_LINES
+    }
+
+    /// Drops the first lines while too many are loaded
+    fn trim_top(&mut self) {
+        let (row, col) = self.textarea.cursor();
+        let mut dropped = 0;
+        let lines = self.line_bytes.len();
+        while self.too_large(lines - dropped) && dropped + KEEP_ROWS < row {
+            self.start += self.line_bytes[dropped];
+            dropped += 1;
+        }
+        if dropped > 0 {
+            let lines = self.raw_lines[dropped..].to_vec();
+            self.line_bytes.drain(..dropped);
+            self.set_lines(lines, (row - dropped, col));
+            self.shift_view(-(dropped as isize));
+        }
+    }
+
+    /// Drops the last lines while too many are loaded, an empty line standing
+    /// for the next one until it is read again
+    fn trim_bottom(&mut self) {
+        let (row, col) = self.textarea.cursor();
+        let mut kept = self.line_bytes.len();
+        while self.too_large(kept) && kept > row + KEEP_ROWS {
+            kept -= 1;
+            self.end -= self.line_bytes[kept];
         }
+        if kept < self.line_bytes.len() {
+            let mut lines = self.raw_lines[..kept].to_vec();
+            lines.push(String::new());
+            self.line_bytes.truncate(kept);
+            self.line_bytes.push(0);
+            self.set_lines(lines, (row, col));
+        }
+    }
+
+    /// Replaces the loaded lines with the lines read, keeping the search
+    fn set_lines(&mut self, raw_lines: Vec<String>, cursor: (usize, usize)) {
+        let pattern = self
+            .textarea
+            .search_pattern()
+            .map(|r| r.as_str().to_string());
+        let (shown, styles): (Vec<String>, Vec<_>) = raw_lines
+            .iter()
+            .map(|line| ansi::parse_line(line, self.ansi_mode))
+            .unzip();
+        self.raw_lines = raw_lines;
+        self.line_styles = styles;
+        self.textarea = TextArea::new(shown);
+        if let Some(pattern) = pattern {
+            // the pattern was valid before
+            let _ = self.textarea.set_search_pattern(pattern);
+        }
+        self.jump_cursor(cursor);
+        if let Some(ref mut search) = self.search {
+            search.matches.clear();
+        }
+        self.find_matches(0);
+    }
+
+    /// Keeps the same lines in view once rows were added or removed above them
+    fn shift_view(&mut self, rows: isize) {
+        let (top, left) = self.view.get();
+        self.view.set((top.saturating_add_signed(rows), left));
+    }
+
+    /// Scrolls the view by `rows`, moving the cursor only to keep it in view
+    fn scroll(&mut self, rows: isize) {
+        let (top, left) = self.view.get();
+        let last_row = self.textarea.lines().len() - 1;
+        let top = top.saturating_add_signed(rows).min(last_row);
+        self.view.set((top, left));
+        let (row, col) = self.textarea.cursor();
+        let bottom = top + self.height.get().max(1) - 1;
+        self.jump_cursor((row.clamp(top, bottom), col));
+    }
+
+    /// Lines in a view of `width` by `height`, scrolled to show the cursor,
+    /// with highlighted lines and search matches coloured
+    pub fn view_lines(&self, width: u16, height: u16) -> Vec<Line<'static>> {
+        let (row, col) = self.textarea.cursor();
+        let (top, left) = self.view.get();
+        let top = scroll_to(top, row, height as usize);
+        let left = scroll_to(left, col, width as usize);
+        self.view.set((top, left));
+        self.height.set(height as usize);
+        let lines = self.textarea.lines();
+        let bottom = lines.len().min(top + height as usize);
+        (top..bottom).map(|row| self.view_line(row, left)).collect()
+    }
+
+    fn view_line(&self, row: usize, left: usize) -> Line<'static> {
+        let line = &self.textarea.lines()[row];
+        let base = self.highlighter.style(line).unwrap_or_default();
+        let mut chars: Vec<char> = line.chars().collect();
+        let mut styles = vec![base; chars.len()];
+        for (range, style) in &self.line_styles[row] {
+            for char_style in &mut styles[range.clone()] {
+                *char_style = base.patch(*style);
+            }
+        }
+        if let Some(regex) = self.textarea.search_pattern() {
+            for found in regex.find_iter(line) {
+                let start = line[..found.start()].chars().count();
+                let end = start + found.as_str().chars().count();
+                for style in &mut styles[start..end] {
+                    *style = style.bg(Color::Blue);
+                }
+            }
+        }
+        let (cursor_row, cursor_col) = self.textarea.cursor();
+        if row == cursor_row {
+            // the cursor may be after the last character
+            chars.push(' ');
+            styles.push(base);
+            for style in styles.iter_mut() {
+                *style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            styles[cursor_col] = styles[cursor_col].add_modifier(Modifier::REVERSED);
+        }
+        let mut spans: Vec<Span> = vec![];
+        let mut text = String::new();
+        let mut text_style = base;
+        let mut width = 0;
+        for (c, style) in chars.into_iter().zip(styles).skip(left) {
+            if style != text_style && !text.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut text), text_style));
+            }
+            text_style = style;
+            if c == '\t' {
+                let spaces = TAB_LENGTH - width % TAB_LENGTH;
+                text.push_str(&" ".repeat(spaces));
+                width += spaces;
+            } else {
+                text.push(c);
+                width += 1;
+            }
+        }
+        spans.push(Span::styled(text, text_style));
+        Line::from(spans)
+    }
+
+    /// Moves to the next line coloured by a highlight rule, down or up. Past
+    /// the loaded lines, moves to the last or first one so that the next page
+    /// is read.
+    fn jump_to_highlight(&mut self, backward: bool) {
+        let (row, _) = self.textarea.cursor();
+        let lines = self.textarea.lines();
+        let is_highlighted = |r: &usize| self.highlighter.style(&lines[*r]).is_some();
+        let found = match backward {
+            true => (0..row).rev().find(is_highlighted),
+            false => (row + 1..lines.len()).find(is_highlighted),
+        };
+        let target = match (found, backward) {
+            (Some(found), _) => found,
+            (None, true) if self.start > 0 => 0,
+            (None, false) if self.end < self.file_size => lines.len() - 1,
+            (None, _) => return,
+        };
+        self.jump_cursor((target, 0));
+    }
+
+    /// Moves the cursor to the row and column, or as close as they exist
+    fn jump_cursor(&mut self, (row, col): (usize, usize)) {
+        let row = row.min(self.textarea.lines().len() - 1);
+        let row = u16::try_from(row).unwrap_or(u16::MAX);
+        let col = u16::try_from(col).unwrap_or(u16::MAX);
+        self.textarea.move_cursor(CursorMove::Jump(row, col));
+    }
+
+    /// Searches the regex from the cursor, down with `/` or up with `?`
+    pub fn search(&mut self, pattern: &str, backward: bool) -> Result<(), String> {
+        // like vim, an empty pattern searches the last one again
+        let pattern = match (pattern, &self.search) {
+            ("", Some(search)) => search.pattern.clone(),
+            ("", None) => return Ok(()),
+            (pattern, _) => pattern.to_string(),
+        };
+        self.set_pattern(&pattern, backward)?;
+        self.jump_to_match(backward, true);
+        Ok(())
+    }
+
+    fn set_pattern(&mut self, pattern: &str, backward: bool) -> Result<(), String> {
+        // checked alone so that the error shows the pattern as typed
+        Regex::new(pattern).map_err(|e| e.to_string())?;
+        let regex = match self.case_mode.ignores_case(pattern) {
+            true => format!("(?i){}", pattern),
+            false => pattern.to_string(),
+        };
+        self.textarea
+            .set_search_pattern(regex)
+            .map_err(|e| e.to_string())?;
+        self.search = Some(Search {
+            pattern: pattern.to_string(),
+            backward,
+            matches: vec![],
+            missing: false,
+        });
+        self.find_matches(0);
+        Ok(())
+    }
+
+    /// Adds the matches from `first_row` to the end
+    fn find_matches(&mut self, first_row: usize) {
+        let (Some(search), Some(regex)) = (&mut self.search, self.textarea.search_pattern()) else {
+            return;
+        };
+        let lines = self.textarea.lines().iter().enumerate().skip(first_row);
+        for (row, line) in lines {
+            for found in regex.find_iter(line) {
+                let col = line[..found.start()].chars().count();
+                search.matches.push((row, col));
+            }
+        }
+    }
+
+    /// Moves to the next match in the direction, the one under the cursor
+    /// counting when `match_cursor` is set. Past the loaded matches, the file
+    /// is searched from the end of the loaded lines.
+    fn jump_to_match(&mut self, backward: bool, match_cursor: bool) {
+        let Some(ref search) = self.search else {
+            return;
+        };
+        let cursor = self.textarea.cursor();
+        let is_next = |m: &&(usize, usize)| match backward {
+            true => **m < cursor || (match_cursor && **m == cursor),
+            false => **m > cursor || (match_cursor && **m == cursor),
+        };
+        let found = match backward {
+            true => search.matches.iter().rev().find(is_next),
+            false => search.matches.iter().find(is_next),
+        };
+        if let Some(&found) = found {
+            self.jump_cursor(found);
+        } else if self.is_whole() {
+            // like vim, the search wraps around the end of the file
+            let wrapped = match backward {
+                true => search.matches.last(),
+                false => search.matches.first(),
+            };
+            if let Some(&wrapped) = wrapped {
+                self.jump_cursor(wrapped);
+            }
+        } else {
+            self.file_search = Some(FileSearch {
+                pattern: search.pattern.clone(),
+                ignore_case: self.case_mode.ignores_case(&search.pattern),
+                offset: match backward {
+                    true => self.start,
+                    false => self.end,
+                },
+                backward,
+            });
+            self.searching = Some(backward);
+        }
+    }
+
+    /// Shows the match found by the search of the file at the offset of its
+    /// line, reading the page starting there unless it is loaded
+    pub fn show_file_match(&mut self, found: Option<u64>) {
+        let Some(backward) = self.searching else {
+            return;
+        };
+        let Some(offset) = found else {
+            self.searching = None;
+            if let Some(ref mut search) = self.search {
+                search.missing = true;
+            }
+            return;
+        };
+        if offset < self.start || self.end <= offset {
+            self.jump = Some(PageRange::At(offset));
+            return;
+        }
+        // the search wrapped around to a loaded line
+        let mut line_start = self.start;
+        let mut row = 0;
+        while row + 1 < self.line_bytes.len() && line_start + self.line_bytes[row] <= offset {
+            line_start += self.line_bytes[row];
+            row += 1;
+        }
+        self.searching = None;
+        self.jump_to_row_match(row, backward);
+    }
+
+    /// Stops waiting for the search of the file, which failed
+    pub fn cancel_file_search(&mut self) {
+        self.searching = None;
+    }
+
+    /// Moves to the first match of the row, or its last one when searching
+    /// backward, or to the row when the viewer's regex does not match it
+    fn jump_to_row_match(&mut self, row: usize, backward: bool) {
+        let matches = self.search.as_ref().map_or(&[][..], |s| &s.matches);
+        let found = match backward {
+            true => matches.iter().rfind(|m| m.0 == row),
+            false => matches.iter().find(|m| m.0 == row),
+        };
+        self.jump_cursor(found.copied().unwrap_or((row, 0)));
+    }
+
+    /// Position of the match under the cursor, from 1, and the match count
+    pub fn match_position(&self) -> Option<(Option<usize>, usize)> {
+        let search = self.search.as_ref()?;
+        let position = search
+            .matches
+            .binary_search(&self.textarea.cursor())
+            .ok()
+            .map(|i| i + 1);
+        Some((position, search.matches.len()))
+    }
+
+    /// Search status shown above the log, like `/error/ 3 of 12 (smart case)`,
+    /// the count being of the loaded matches unless the whole file is
+    pub fn search_status(&self) -> Option<String> {
+        let search = self.search.as_ref()?;
+        let (position, count) = self.match_position()?;
+        let delimiter = match search.backward {
+            true => '?',
+            false => '/',
+        };
+        let found = match (position, count) {
+            _ if self.searching.is_some() => "searching the file".to_string(),
+            _ if search.missing => "no match".to_string(),
+            (_, 0) => "no match".to_string(),
+            (Some(position), count) => format!("{} of {}", position, count),
+            (None, 1) => "1 match".to_string(),
+            (None, count) => format!("{} matches", count),
+        };
+        // the count only covers the loaded pages
+        let found = match self.is_whole() || self.searching.is_some() || search.missing {
+            true => found,
+            false => format!("{} loaded", found),
+        };
+        Some(format!(
+            "{}{}{} {} ({})",
+            delimiter,
+            search.pattern,
+            delimiter,
+            found,
+            self.case_mode.name()
+        ))
     }
 
     pub fn send_char(&mut self, c_sent: char) {
@@ -23,15 +606,236 @@ impl<'a> Editor<'a> {
             'b' => self.textarea.move_cursor(CursorMove::WordBack),
             '^' => self.textarea.move_cursor(CursorMove::Head),
             '$' => self.textarea.move_cursor(CursorMove::End),
-            'e' => self.textarea.scroll((1, 0)),
-            'y' => self.textarea.scroll((-1, 0)),
-            'D' => self.textarea.scroll(Scrolling::HalfPageDown),
-            'U' => self.textarea.scroll(Scrolling::HalfPageUp),
-            'F' => self.textarea.scroll(Scrolling::PageDown),
-            'B' => self.textarea.scroll(Scrolling::PageUp),
+            'e' => self.scroll(1),
+            'y' => self.scroll(-1),
+            'D' => self.scroll(self.height.get() as isize / 2),
+            'U' => self.scroll(-(self.height.get() as isize) / 2),
+            'F' => self.scroll(self.height.get() as isize),
+            'B' => self.scroll(-(self.height.get() as isize)),
+            'g' if self.start > 0 => self.jump = Some(PageRange::Head),
             'g' => self.textarea.move_cursor(CursorMove::Top),
+            'G' if self.end < self.file_size => self.jump = Some(PageRange::Tail),
             'G' => self.textarea.move_cursor(CursorMove::Bottom),
+            'f' => {
+                self.follow = !self.follow;
+                if self.follow {
+                    self.send_char('G');
+                }
+            }
+            'n' | 'N' => {
+                if let Some(ref search) = self.search {
+                    let backward = search.backward != (c_sent == 'N');
+                    self.jump_to_match(backward, false);
+                }
+            }
+            'a' => {
+                self.ansi_mode = self.ansi_mode.next();
+                let raw_lines = std::mem::take(&mut self.raw_lines);
+                self.set_lines(raw_lines, self.textarea.cursor());
+            }
+            ']' => self.jump_to_highlight(false),
+            '[' => self.jump_to_highlight(true),
+            'i' => {
+                self.case_mode = self.case_mode.next();
+                if let Some(search) = self.search.take() {
+                    // the pattern was valid, and stays so with another case
+                    let _ = self.set_pattern(&search.pattern, search.backward);
+                }
+            }
             _ => (),
         }
     }
 }
+
+/// Whether the pattern has an uppercase letter, leaving out the escapes like
+/// `\S` or `\W`
+fn has_uppercase(pattern: &str) -> bool {
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if !escaped && c.is_uppercase() {
+            return true;
+        }
+        escaped = !escaped && c == '\\';
+    }
+    false
+}
+
+/// First row or column of a view of `length` showing `cursor`, moved from
+/// `top` only as much as needed
+fn scroll_to(top: usize, cursor: usize, length: usize) -> usize {
+    if cursor < top {
+        cursor
+    } else if top + length <= cursor {
+        cursor + 1 - length
+    } else {
+        top
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LogConfig;
+
+    static FILE: &str = "one\ntwo\nthree\nfour";
+
+    fn page(range: PageRange, start: usize, end: usize, file: &str) -> LogPage {
+        let bytes = &file.as_bytes()[start..end];
+        LogPage::from_bytes(range, bytes, start as u64, file.len() as u64)
+    }
+
+    fn editor(page: LogPage) -> Editor<'static> {
+        let highlighter = LogHighlighter::new(&LogConfig::default()).unwrap();
+        Editor::new("slurm-1.out", page, highlighter)
+    }
+
+    #[test]
+    fn prepend_joins_the_first_line() {
+        let mut editor = editor(page(PageRange::Tail, 10, FILE.len(), FILE));
+        // "ree" is dropped, being cut
+        assert_eq!(editor.textarea.lines(), ["four"]);
+        assert_eq!(editor.start, 14);
+        editor.add_page(
+            PageRange::Before(14),
+            page(PageRange::Before(14), 2, 14, FILE),
+        );
+        assert_eq!(editor.textarea.lines(), ["two", "three", "four"]);
+        assert_eq!(editor.line_bytes, [4, 6, 4]);
+        assert_eq!((editor.start, editor.end), (4, 18));
+        // the cursor stays on the same line
+        assert_eq!(editor.textarea.cursor(), (2, 0));
+        editor.add_page(PageRange::Before(4), page(PageRange::Before(4), 0, 4, FILE));
+        assert_eq!(editor.textarea.lines(), ["one", "two", "three", "four"]);
+        assert!(editor.is_whole());
+    }
+
+    #[test]
+    fn append_joins_the_last_line() {
+        let mut editor = editor(page(PageRange::Head, 0, FILE.len(), FILE));
+        assert_eq!(editor.textarea.lines(), ["one", "two", "three", "four"]);
+        // the job wrote the end of the last line, then a new one
+        let grown = format!("{} more\nfive", FILE);
+        let appended = page(PageRange::After(18), 18, grown.len(), &grown);
+        editor.add_page(PageRange::After(18), appended);
+        assert_eq!(
+            editor.textarea.lines(),
+            ["one", "two", "three", "four more", "five"]
+        );
+        assert_eq!(editor.line_bytes, [4, 4, 6, 10, 4]);
+        assert_eq!((editor.end, editor.file_size), (28, 28));
+    }
+
+    #[test]
+    fn append_shows_the_last_write_of_a_progress_bar() {
+        let first = "step 1/3\r";
+        let mut editor = editor(page(PageRange::Head, 0, first.len(), first));
+        let grown = format!("{}step 3/3\n", first);
+        let appended = page(PageRange::After(9), 9, grown.len(), &grown);
+        editor.add_page(PageRange::After(9), appended);
+        assert_eq!(editor.textarea.lines(), ["step 3/3", ""]);
+        assert_eq!(editor.raw_lines[0], "step 1/3\rstep 3/3");
+    }
+
+    #[test]
+    fn search_counts_loaded_matches_and_goes_on_in_the_file() {
+        let mut editor = editor(page(PageRange::Tail, 6, FILE.len(), FILE));
+        assert_eq!(editor.textarea.lines(), ["three", "four"]);
+        editor.search("o", false).unwrap();
+        // "four" is the only loaded line with an o, after the cursor
+        assert_eq!(editor.textarea.cursor(), (1, 1));
+        assert_eq!(
+            editor.search_status().as_deref(),
+            Some("/o/ 1 of 1 loaded (smart case)")
+        );
+        editor.send_char('n');
+        let search = editor.file_search.take().unwrap();
+        assert_eq!((search.offset, search.backward), (18, false));
+        assert_eq!(
+            editor.search_status().as_deref(),
+            Some("/o/ searching the file (smart case)")
+        );
+        // the search wrapped around to the first line, which is read
+        editor.show_file_match(search.find_in(FILE.as_bytes()).unwrap());
+        assert_eq!(editor.next_page(), Some(PageRange::At(0)));
+        editor.add_page(PageRange::At(0), page(PageRange::At(0), 0, 8, FILE));
+        assert_eq!(editor.textarea.lines(), ["one", "two", ""]);
+        assert_eq!(editor.textarea.cursor(), (0, 0));
+    }
+
+    #[test]
+    fn search_of_a_loaded_match_or_of_no_match() {
+        let mut editor = editor(page(PageRange::Tail, 2, FILE.len(), FILE));
+        assert_eq!(editor.textarea.lines(), ["two", "three", "four"]);
+        editor.search("three", true).unwrap();
+        assert_eq!(editor.textarea.cursor(), (1, 0));
+        editor.send_char('n');
+        let search = editor.file_search.take().unwrap();
+        assert_eq!((search.offset, search.backward), (4, true));
+        // the only match is the loaded one
+        editor.show_file_match(Some(8));
+        assert_eq!(editor.textarea.cursor(), (1, 0));
+        assert_eq!(editor.next_page(), Some(PageRange::Before(4)));
+        editor.search("nothing", false).unwrap();
+        let search = editor.file_search.take().unwrap();
+        editor.show_file_match(search.find_in(FILE.as_bytes()).unwrap());
+        assert_eq!(
+            editor.search_status().as_deref(),
+            Some("/nothing/ no match (smart case)")
+        );
+    }
+
+    static LOG: &str = "Error one\nerror two\nERROR three\n";
+
+    #[test]
+    fn smart_case_leaves_out_escapes() {
+        assert!(CaseMode::Smart.ignores_case("error"));
+        assert!(CaseMode::Smart.ignores_case(r"\S+error\W\D\B"));
+        assert!(!CaseMode::Smart.ignores_case("Error"));
+        assert!(!CaseMode::Smart.ignores_case(r"\\Error"));
+        assert!(!CaseMode::Sensitive.ignores_case("error"));
+        assert!(CaseMode::Insensitive.ignores_case("Error"));
+    }
+
+    #[test]
+    fn search_counts_by_case_mode() {
+        let mut editor = editor(page(PageRange::Head, 0, LOG.len(), LOG));
+        editor.search(r"\S*error", false).unwrap();
+        assert_eq!(editor.match_position(), Some((Some(1), 3)));
+        editor.search("Error", false).unwrap();
+        assert_eq!(editor.match_position(), Some((Some(1), 1)));
+        assert_eq!(editor.textarea.cursor(), (0, 0));
+        // the case mode goes on to match case, then ignore case
+        editor.send_char('i');
+        assert_eq!(editor.match_position(), Some((Some(1), 1)));
+        editor.send_char('i');
+        assert_eq!(editor.match_position(), Some((Some(1), 3)));
+        assert_eq!(
+            editor.search_status().as_deref(),
+            Some("/Error/ 1 of 3 (ignore case)")
+        );
+    }
+
+    #[test]
+    fn n_follows_the_search_direction_and_capital_n_goes_back() {
+        let mut editor = editor(page(PageRange::Head, 0, LOG.len(), LOG));
+        editor.search("error", false).unwrap();
+        assert_eq!(editor.textarea.cursor(), (0, 0));
+        editor.send_char('n');
+        assert_eq!(editor.textarea.cursor(), (1, 0));
+        assert_eq!(editor.match_position(), Some((Some(2), 3)));
+        editor.send_char('N');
+        assert_eq!(editor.textarea.cursor(), (0, 0));
+        // the whole file is loaded, so the search wraps around
+        editor.send_char('N');
+        assert_eq!(editor.textarea.cursor(), (2, 0));
+        editor.search("error", true).unwrap();
+        // a backward search starts with the match under the cursor
+        assert_eq!(editor.textarea.cursor(), (2, 0));
+        editor.send_char('n');
+        assert_eq!(editor.textarea.cursor(), (1, 0));
+        editor.send_char('N');
+        assert_eq!(editor.textarea.cursor(), (2, 0));
+        editor.send_char('j');
+        assert_eq!(editor.match_position(), Some((None, 3)));
+    }
+}
@@ -1,5 +1,7 @@
-use crate::jobs::job_parser::JobFields;
+use crate::jobs::job_id::{format_ranges, ArrayPart, JobId};
+use crate::jobs::job_parser::{JobFields, JobState, NumberOrCol};
 use crate::App;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Clone, Debug)]
 pub struct JobQueryInfo {
@@ -8,10 +10,59 @@ pub struct JobQueryInfo {
     pub time: JobTime,
     pub changed: bool,
     pub job_display: Vec<JobFields>,
-    pub folded_jobs: Vec<bool>,
+    /// Steps of every job, shown under the unfolded ones
+    pub steps: Vec<JobFields>,
+    pub unfolded_jobs: HashSet<String>,
+    /// Tasks shown under the unfolded arrays
+    pub task_filter: TaskFilter,
     pub efficiency_display: bool,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskFilter {
+    All,
+    /// Every task that did not complete, whatever the reason
+    Failed,
+    Pending,
+    Running,
+    Completed,
+}
+
+impl TaskFilter {
+    pub fn next(&self) -> TaskFilter {
+        match self {
+            TaskFilter::All => TaskFilter::Failed,
+            TaskFilter::Failed => TaskFilter::Pending,
+            TaskFilter::Pending => TaskFilter::Running,
+            TaskFilter::Running => TaskFilter::Completed,
+            TaskFilter::Completed => TaskFilter::All,
+        }
+    }
+
+    pub fn matches(&self, state: &JobState) -> bool {
+        match self {
+            TaskFilter::All => true,
+            TaskFilter::Failed => matches!(
+                state,
+                JobState::Failed(_) | JobState::Cancelled(_) | JobState::Unknown(_)
+            ),
+            TaskFilter::Pending => matches!(state, JobState::Pending),
+            TaskFilter::Running => matches!(state, JobState::Running | JobState::Suspended),
+            TaskFilter::Completed => matches!(state, JobState::Completed),
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            TaskFilter::All => "all",
+            TaskFilter::Failed => "failed",
+            TaskFilter::Pending => "pending",
+            TaskFilter::Running => "running",
+            TaskFilter::Completed => "completed",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum JobTime {
     Finished,
@@ -20,14 +71,16 @@ pub enum JobTime {
 }
 
 impl JobQueryInfo {
-    pub fn from_result(job_list: Vec<JobFields>, app: &App) -> Self {
+    pub fn from_result(job_list: Vec<JobFields>, steps: Vec<JobFields>, app: &App) -> Self {
         let mut jqi = JobQueryInfo {
             refresh: app.cli.refresh,
             time: JobTime::All,
             job_list: job_list.clone(),
             changed: false,
-            folded_jobs: vec![false; job_list.len()],
             job_display: vec![],
+            steps,
+            unfolded_jobs: HashSet::new(),
+            task_filter: TaskFilter::All,
             efficiency_display: false,
         };
         jqi.make_display();
@@ -37,31 +90,135 @@ impl JobQueryInfo {
 
     pub fn make_display(&mut self) {
         let mut job_display = vec![];
-        let mut opt_job_array_display: Option<JobArrayDisplay> = None;
+        // each array is shown where its first task is, with its index in job_display
+        let mut arrays: HashMap<u64, (usize, JobArrayDisplay)> = HashMap::new();
+        // het jobs likewise, where their first component is
+        let mut het_jobs: HashMap<u64, (usize, HetJobDisplay)> = HashMap::new();
         self.job_list.iter().for_each(|j| {
-            if j.job_id.contains('_') && !j.job_id.contains('[') {
-                let mut split = j.job_id.split('_');
-                let array_jid = split.next().unwrap();
-                let array_num = split.next().unwrap().parse::<u32>().unwrap();
-                opt_job_array_display = match opt_job_array_display.clone() {
-                    None => Some(JobArrayDisplay::new(array_jid, array_num, j.clone())),
-                    Some(mut jobarr) => {
-                        jobarr.update(array_num);
-                        Some(jobarr)
+            let id = j.id();
+            if let Some(array_jid) = id.as_ref().and_then(JobId::array_id) {
+                let task_ids = id.as_ref().map_or(vec![], JobId::task_ids);
+                match arrays.get_mut(&array_jid) {
+                    Some((_, jobarr)) => jobarr.update(&task_ids, &j.state),
+                    None => {
+                        let jobarr = JobArrayDisplay::new(array_jid, &task_ids, j.clone());
+                        arrays.insert(array_jid, (job_display.len(), jobarr));
+                        job_display.push(j.clone());
                     }
                 }
-            } else {
-                if let Some(jobarr) = opt_job_array_display.clone() {
-                    job_display.push(jobarr.get_as_field());
-                    opt_job_array_display = None;
+            } else if let Some(het_jid) = id.as_ref().and_then(JobId::het_id) {
+                match het_jobs.get_mut(&het_jid) {
+                    Some((_, hetjob)) => hetjob.components.push(j.clone()),
+                    None => {
+                        let hetjob = HetJobDisplay {
+                            id: het_jid,
+                            components: vec![j.clone()],
+                        };
+                        het_jobs.insert(het_jid, (job_display.len(), hetjob));
+                        job_display.push(j.clone());
+                    }
                 }
+            } else {
                 job_display.push(j.clone())
             }
         });
-        if let Some(jobarr) = opt_job_array_display {
-            job_display.push(jobarr.get_as_field());
+        for (index, jobarr) in arrays.into_values() {
+            job_display[index] = jobarr.get_as_field();
+        }
+        for (index, hetjob) in het_jobs.into_values() {
+            job_display[index] = hetjob.get_as_field();
+        }
+        self.job_display = job_display
+            .into_iter()
+            .flat_map(|job| {
+                let key = fold_key(&job);
+                let mut rows = vec![];
+                if self.unfolded_jobs.contains(&key) && job.group_summary.is_some() {
+                    let members = match group_id(&job) {
+                        Some(group_id) if is_array(&job) => self.tasks_of(group_id),
+                        Some(group_id) => self.components_of(group_id),
+                        None => vec![],
+                    };
+                    rows.push(job);
+                    for member in members {
+                        rows.extend(self.with_steps(member));
+                    }
+                } else {
+                    rows.extend(self.with_steps(job));
+                }
+                rows
+            })
+            .collect();
+    }
+
+    /// The job, followed by its steps when it is unfolded
+    fn with_steps(&self, job: JobFields) -> Vec<JobFields> {
+        let steps = match job.id() {
+            Some(id) if self.unfolded_jobs.contains(&fold_key(&job)) => self.steps_of(&id.job()),
+            _ => vec![],
+        };
+        std::iter::once(job).chain(steps).collect()
+    }
+
+    /// Rows of the tasks of the array that pass the task filter
+    pub fn tasks_of(&self, array_id: u64) -> Vec<JobFields> {
+        self.job_list
+            .iter()
+            .filter(|job| job.id().and_then(|id| id.array_id()) == Some(array_id))
+            .filter(|job| self.task_filter.matches(&job.state))
+            .cloned()
+            .collect()
+    }
+
+    /// Rows of the components of the het job, in offset order
+    fn components_of(&self, het_id: u64) -> Vec<JobFields> {
+        let mut components: Vec<JobFields> = self
+            .job_list
+            .iter()
+            .filter(|job| job.id().and_then(|id| id.het_id()) == Some(het_id))
+            .cloned()
+            .collect();
+        components.sort_by_key(|job| job.id());
+        components
+    }
+
+    /// `--array` option selecting the tasks shown for the array of the row at
+    /// `index`, which may be the array itself or one of its tasks
+    pub fn array_spec(&self, index: usize) -> Option<String> {
+        let job = &self.job_display[index];
+        let array_id = job.id()?.array_id()?;
+        let task_ids: Vec<u32> = self
+            .tasks_of(array_id)
+            .iter()
+            .flat_map(|task| task.id().map_or(vec![], |id| id.task_ids()))
+            .collect();
+        if task_ids.is_empty() {
+            return None;
+        }
+        Some(format!("--array={}", format_ranges(&task_ids)))
+    }
+
+    fn steps_of(&self, job_id: &JobId) -> Vec<JobFields> {
+        self.steps
+            .iter()
+            .filter(|step| step.id().is_some_and(|id| id.belongs_to(job_id)))
+            .cloned()
+            .collect()
+    }
+
+    /// Shows or hides the steps of the job displayed at `index`, or of the job of
+    /// the step, or the tasks of an array, or the components of a het job.
+    /// Returns the new index of the row.
+    pub fn toggle_fold(&mut self, index: usize) -> usize {
+        let key = fold_key(&self.job_display[index]);
+        if !self.unfolded_jobs.remove(&key) {
+            self.unfolded_jobs.insert(key.clone());
         }
-        self.job_display = job_display;
+        self.make_display();
+        self.job_display
+            .iter()
+            .position(|job| fold_key(job) == key)
+            .unwrap_or(index)
     }
 
     pub fn default(app: &App) -> Self {
@@ -71,41 +228,281 @@ impl JobQueryInfo {
             time: JobTime::All,
             changed: false,
             job_display: Vec::new(),
-            folded_jobs: Vec::new(),
+            steps: Vec::new(),
+            unfolded_jobs: HashSet::new(),
+            task_filter: TaskFilter::All,
             efficiency_display: false,
         }
     }
 }
-#[derive(Clone)]
+/// Id under which the row is unfolded: the job of a step, or the array id for
+/// a whole array, which keeps it unfolded when its tasks change
+fn fold_key(job: &JobFields) -> String {
+    if job.group_summary.is_some() {
+        if let Some(group_id) = group_id(job) {
+            return group_id.to_string();
+        }
+    }
+    match job.id() {
+        Some(id) => id.job().to_string(),
+        None => job.job_id.clone(),
+    }
+}
+
+/// Array or het job id of a row standing for the whole array `12_[1-5]` or
+/// het job `12`
+fn group_id(job: &JobFields) -> Option<u64> {
+    job.id().map(|id| id.base)
+}
+
+/// Whether the row stands for several tasks of an array
+fn is_array(job: &JobFields) -> bool {
+    job.id().is_some_and(|id| id.is_task_range())
+}
+
 struct JobArrayDisplay {
-    min: u32,
-    max: u32,
-    id: String,
+    id: u64,
+    tasks: Vec<(u32, JobState)>,
     job_field: JobFields,
 }
 
 impl JobArrayDisplay {
-    fn new(id: &str, num: u32, job_field: JobFields) -> Self {
-        JobArrayDisplay {
-            min: num,
-            max: num,
-            id: id.to_string(),
+    fn new(id: u64, task_ids: &[u32], job_field: JobFields) -> Self {
+        let mut jobarr = JobArrayDisplay {
+            id,
+            tasks: vec![],
             job_field,
+        };
+        jobarr.update(task_ids, &jobarr.job_field.state.clone());
+        jobarr
+    }
+
+    fn update(&mut self, task_ids: &[u32], state: &JobState) {
+        self.tasks
+            .extend(task_ids.iter().map(|&id| (id, state.clone())));
+    }
+
+    fn get_as_field(mut self) -> JobFields {
+        let task_ids: Vec<u32> = self.tasks.iter().map(|(id, _)| *id).collect();
+        let id = JobId {
+            base: self.id,
+            array: Some(ArrayPart::Range {
+                tasks: task_ids,
+                throttle: None,
+            }),
+            het_offset: None,
+            step: None,
+        };
+        self.job_field.job_id = id.to_string();
+        let states: Vec<&JobState> = self.tasks.iter().map(|(_, state)| state).collect();
+        self.job_field.group_summary = Some(state_summary(&states));
+        // the worst task gives its state to the array
+        if let Some(state) = worst_state(&states) {
+            self.job_field.state = state;
         }
+        self.job_field
     }
+}
+
+struct HetJobDisplay {
+    id: u64,
+    components: Vec<JobFields>,
+}
 
-    fn update(&mut self, num: u32) {
-        if num > self.max {
-            self.max = num;
+impl HetJobDisplay {
+    /// Row of the whole het job, with the resources of every component. The
+    /// components run side by side, so the het job lasts as long as the longest.
+    fn get_as_field(self) -> JobFields {
+        let components = &self.components;
+        let leader = components
+            .iter()
+            .min_by_key(|job| job.id())
+            .unwrap_or(&components[0]);
+        let states: Vec<&JobState> = components.iter().map(|job| &job.state).collect();
+        let sum = |value: fn(&JobFields) -> &NumberOrCol| {
+            NumberOrCol::Value(components.iter().map(|job| number(value(job))).sum())
+        };
+        let max = |value: fn(&JobFields) -> &NumberOrCol| {
+            NumberOrCol::Value(
+                components
+                    .iter()
+                    .map(|job| number(value(job)))
+                    .max()
+                    .unwrap_or(0),
+            )
+        };
+        let mut partitions: Vec<&str> = vec![];
+        for job in components {
+            if !partitions.contains(&job.partition.as_str()) {
+                partitions.push(&job.partition);
+            }
         }
-        if num < self.min {
-            self.min = num
+        JobFields {
+            job_id: self.id.to_string(),
+            partition: partitions.join(","),
+            alloc_cpus: sum(|job| &job.alloc_cpus),
+            reqmem: sum(|job| &job.reqmem),
+            maxrss: sum(|job| &job.maxrss),
+            cpu_time_raw: sum(|job| &job.cpu_time_raw),
+            elapsed: max(|job| &job.elapsed),
+            time_limit: max(|job| &job.time_limit),
+            state: worst_state(&states).unwrap_or(leader.state.clone()),
+            group_summary: Some(format!("het: {}", state_summary(&states))),
+            ..leader.clone()
         }
     }
+}
 
-    fn get_as_field(mut self) -> JobFields {
-        let name = self.id + "[" + &self.min.to_string() + "-" + &self.max.to_string() + "]";
-        self.job_field.job_id = name;
-        self.job_field
+fn number(value: &NumberOrCol) -> usize {
+    value.clone().take().unwrap_or(0)
+}
+
+/// Count of tasks or components by state, like `92 CD / 3 F / 5 R`, the most
+/// frequent first
+fn state_summary(states: &[&JobState]) -> String {
+    let mut counts: Vec<(String, usize, u8)> = vec![];
+    for state in states {
+        let name = state.short_name();
+        match counts.iter_mut().find(|(n, _, _)| *n == name) {
+            Some((_, count, _)) => *count += 1,
+            None => counts.push((name, 1, state.severity())),
+        }
+    }
+    counts.sort_by(|(_, c1, s1), (_, c2, s2)| c2.cmp(c1).then(s2.cmp(s1)));
+    counts
+        .iter()
+        .map(|(name, count, _)| format!("{} {}", count, name))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// The worst state, which the whole array or het job is shown with
+fn worst_state(states: &[&JobState]) -> Option<JobState> {
+    states
+        .iter()
+        .max_by_key(|state| state.severity())
+        .map(|&state| state.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::fixture::DEFAULT_FIXTURE_DIR;
+    use crate::jobs::job_handler::job_steps;
+
+    fn fixture_info() -> JobQueryInfo {
+        let sacct = std::fs::read_to_string(format!("{}/sacct.txt", DEFAULT_FIXTURE_DIR)).unwrap();
+        let rows = JobFields::from_sacct_str(&sacct).unwrap();
+        let mut info = JobQueryInfo {
+            refresh: false,
+            job_list: rows
+                .iter()
+                .filter(|job| !job.partition.is_empty() && job.state != JobState::Header)
+                .cloned()
+                .collect(),
+            time: JobTime::All,
+            changed: false,
+            job_display: vec![],
+            steps: job_steps(&rows),
+            unfolded_jobs: HashSet::new(),
+            task_filter: TaskFilter::All,
+            efficiency_display: false,
+        };
+        info.make_display();
+        info
+    }
+
+    fn displayed_ids(info: &JobQueryInfo) -> Vec<&str> {
+        info.job_display
+            .iter()
+            .map(|job| job.job_id.as_str())
+            .collect()
+    }
+
+    fn row<'a>(info: &'a JobQueryInfo, job_id: &str) -> &'a JobFields {
+        info.job_display
+            .iter()
+            .find(|job| job.job_id == job_id)
+            .unwrap()
+    }
+
+    #[test]
+    fn steps_show_under_unfolded_jobs() {
+        let mut info = fixture_info();
+        let index = displayed_ids(&info)
+            .iter()
+            .position(|&id| id == "1001")
+            .unwrap();
+        assert_eq!(info.toggle_fold(index), index);
+        assert_eq!(
+            displayed_ids(&info)[index..index + 3],
+            ["1001", "1001.batch", "1001.extern"]
+        );
+        // folding from a step folds its job
+        assert_eq!(info.toggle_fold(index + 2), index);
+        assert_eq!(displayed_ids(&info)[index + 1], "1002");
+    }
+
+    #[test]
+    fn arrays_are_grouped_whatever_the_order_of_their_tasks() {
+        let info = fixture_info();
+        let ids = displayed_ids(&info);
+        assert!(!ids
+            .iter()
+            .any(|id| id.starts_with("1003_") && !id.contains('[')));
+        let array = row(&info, "1003_[1-4,6-8]");
+        assert_eq!(
+            array.group_summary.as_deref(),
+            Some("3 PD / 2 CD / 1 F / 1 OOM")
+        );
+        // coloured by its worst task
+        assert_eq!(array.state, JobState::from_str("FAILED"));
+    }
+
+    #[test]
+    fn unfolded_arrays_show_the_filtered_tasks() {
+        let mut info = fixture_info();
+        let index = displayed_ids(&info)
+            .iter()
+            .position(|&id| id == "1003_[1-4,6-8]")
+            .unwrap();
+        info.toggle_fold(index);
+        assert_eq!(
+            displayed_ids(&info)[index..index + 6],
+            [
+                "1003_[1-4,6-8]",
+                "1003_1",
+                "1003_2",
+                "1003_3",
+                "1003_4",
+                "1003_[6-8]"
+            ]
+        );
+        info.task_filter = TaskFilter::Failed;
+        info.make_display();
+        assert_eq!(
+            displayed_ids(&info)[index..index + 4],
+            ["1003_[1-4,6-8]", "1003_3", "1003_4", "1004"]
+        );
+        assert_eq!(info.array_spec(index).as_deref(), Some("--array=3-4"));
+    }
+
+    #[test]
+    fn het_jobs_sum_the_resources_of_their_components() {
+        let mut info = fixture_info();
+        let het_job = row(&info, "1008");
+        assert_eq!(het_job.partition, "cpu,gpu");
+        assert_eq!(number(&het_job.alloc_cpus), 12);
+        assert_eq!(het_job.state, JobState::Running);
+        assert_eq!(het_job.group_summary.as_deref(), Some("het: 1 R / 1 CD"));
+        let index = displayed_ids(&info)
+            .iter()
+            .position(|&id| id == "1008")
+            .unwrap();
+        info.toggle_fold(index);
+        assert_eq!(
+            displayed_ids(&info)[index..index + 3],
+            ["1008", "1008+0", "1008+1"]
+        );
     }
 }
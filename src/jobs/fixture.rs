@@ -0,0 +1,246 @@
+//! Offline source reading recorded cluster data from a fixture directory:
+//!
+//! ```text
+//! <dir>/sacct.txt   output of `sacct -P` with the columns of FORMAT_STR,
+//!                   or <dir>/sacct.json, output of `sacct --json`
+//! <dir>/files/      mirror of the cluster file system, so that the log
+//!                   /home/jdoe/slurm-12.out lives in <dir>/files/home/jdoe/slurm-12.out
+//! ```
+use super::job_action::JobAction;
+use super::job_handler;
+use super::job_parser::{JobFields, JobState};
+use super::job_source::{JobDetails, JobSource, TimeWindow};
+use super::log_page::{FileSearch, LogPage, PageRange};
+use super::log_paths;
+use super::log_paths::LogSearch;
+use super::sacct_json;
+use crate::config::LogConfig;
+use color_eyre::eyre::{Context, Result};
+use std::path::{Path, PathBuf};
+
+pub static DEFAULT_FIXTURE_DIR: &str = "test_data/fixture";
+static SACCT_FILE: &str = "sacct.txt";
+static SACCT_JSON_FILE: &str = "sacct.json";
+static FILES_DIR: &str = "files";
+// same depth as the script search on a real cluster
+static MAX_SCRIPT_DEPTH: usize = 2;
+
+pub struct FixtureSource {
+    dir: PathBuf,
+    log_search: LogSearch,
+}
+
+impl FixtureSource {
+    pub fn new(dir: &Path, log_config: &LogConfig) -> Self {
+        FixtureSource {
+            dir: dir.to_path_buf(),
+            log_search: LogSearch::new(log_config),
+        }
+    }
+
+    /// Location inside the fixture of a path on the cluster
+    fn local_path(&self, cluster_path: &str) -> PathBuf {
+        self.dir
+            .join(FILES_DIR)
+            .join(cluster_path.trim_start_matches('/'))
+    }
+
+    /// Files under `dir` down to `max_depth`, like `find -maxdepth`
+    fn find(
+        &self,
+        dir: &Path,
+        max_depth: usize,
+        matches: &impl Fn(&str) -> bool,
+        found: &mut Vec<String>,
+    ) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if max_depth > 1 {
+                    self.find(&path, max_depth - 1, matches, found);
+                }
+            } else if matches(&entry.file_name().to_string_lossy()) {
+                let files_root = self.dir.join(FILES_DIR);
+                let relative = path.strip_prefix(files_root).unwrap_or(&path);
+                found.push(format!("/{}", relative.display()));
+            }
+        }
+    }
+}
+
+impl JobSource for FixtureSource {
+    /// The whole recording is returned, whatever the time window
+    fn list_jobs(&self, _window: &TimeWindow) -> Result<Vec<JobFields>> {
+        let json_path = self.dir.join(SACCT_JSON_FILE);
+        if json_path.exists() {
+            return JobFields::from_sacct_json(&read_fixture(&json_path)?);
+        }
+        JobFields::from_sacct_str(&read_fixture(&self.dir.join(SACCT_FILE))?)
+    }
+
+    /// Files matching the log patterns of the job, or else named after the job.
+    /// Recorded jobs are not running, and their user and nodes are unknown.
+    fn list_logs(&self, job: &JobFields) -> Result<Vec<String>> {
+        let script =
+            log_paths::script_path(job).and_then(|path| read_fixture(&self.local_path(&path)).ok());
+        let mut found = vec![];
+        let patterns = self.log_search.patterns(job, script.as_deref());
+        let paths = patterns
+            .iter()
+            .flat_map(|pattern| log_paths::expand(pattern, job, None, None));
+        for path in paths {
+            let path = Path::new(&path);
+            let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+                continue;
+            };
+            let name = name.to_string_lossy();
+            let matches = |file_name: &str| log_paths::glob_matches(&name, file_name);
+            let dir = log_paths::unescape_glob(&dir.display().to_string());
+            self.find(&self.local_path(&dir), 1, &matches, &mut found);
+        }
+        if found.is_empty() && job.state != JobState::Pending {
+            let fragment = job_handler::log_name_fragment(&job.job_id);
+            let matches = |name: &str| job_handler::names_job(name, &fragment);
+            self.find(
+                &self.local_path(&job.workdir),
+                self.log_search.find_depth,
+                &matches,
+                &mut found,
+            );
+        }
+        found.sort();
+        found.dedup();
+        Ok(found)
+    }
+
+    fn list_scripts(&self, dir: &str) -> Result<Vec<String>> {
+        let mut found = vec![];
+        self.find(
+            &self.local_path(dir),
+            MAX_SCRIPT_DEPTH,
+            &job_handler::is_batch_script,
+            &mut found,
+        );
+        found.sort();
+        Ok(found)
+    }
+
+    fn read_log(&self, path: &str) -> Result<String> {
+        read_fixture(&self.local_path(path))
+    }
+
+    /// Recorded logs only grow when edited by hand, which following shows
+    fn read_log_page(&self, path: &str, range: PageRange) -> Result<LogPage> {
+        let local_path = self.local_path(path);
+        let bytes = std::fs::read(&local_path)
+            .wrap_err_with(|| format!("Could not read fixture {}", local_path.display()))?;
+        let file_size = bytes.len() as u64;
+        let start = range.start(file_size).min(file_size);
+        let end = (start + range.read_len()).min(file_size);
+        let page = &bytes[start as usize..end as usize];
+        Ok(LogPage::from_bytes(range, page, start, file_size))
+    }
+
+    fn search_log(&self, path: &str, search: &FileSearch) -> Result<Option<u64>> {
+        let local_path = self.local_path(path);
+        let bytes = std::fs::read(&local_path)
+            .wrap_err_with(|| format!("Could not read fixture {}", local_path.display()))?;
+        Ok(search.find_in(&bytes)?)
+    }
+
+    /// Fields recorded by sacct, scontrol knowing nothing of recorded jobs
+    fn job_details(&self, job_id: &str) -> Result<JobDetails> {
+        let json_path = self.dir.join(SACCT_JSON_FILE);
+        if json_path.exists() {
+            return sacct_json::job_details(&read_fixture(&json_path)?, job_id);
+        }
+        job_handler::sacct_row_details(&read_fixture(&self.dir.join(SACCT_FILE))?, job_id)
+    }
+
+    /// Recorded data cannot change: only tells what would have been run
+    fn run_action(&self, action: &JobAction) -> Result<String> {
+        let (cmd, args) = action.command();
+        Ok(format!(
+            "Fixture data, nothing was run: {} {}",
+            cmd,
+            args.join(" ")
+        ))
+    }
+}
+
+fn read_fixture(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Could not read fixture {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::job_source::TimeBound;
+
+    static EVALUATE_LOG: &str = "/home/jdoe/evaluation/results/v1/logs/evaluate-1010_1.out";
+
+    fn logs(config: &str, job_id: &str) -> Vec<String> {
+        let config: LogConfig = toml::from_str(config).unwrap();
+        let source = FixtureSource::new(Path::new(DEFAULT_FIXTURE_DIR), &config);
+        let window = TimeWindow {
+            start: TimeBound::HoursAgo(24),
+            end: None,
+        };
+        let jobs = source.list_jobs(&window).unwrap();
+        let job = jobs.iter().find(|job| job.job_id == job_id).unwrap();
+        source.list_logs(job).unwrap()
+    }
+
+    #[test]
+    fn recorded_logs_are_found_and_read() {
+        assert_eq!(logs("", "1001"), ["/home/jdoe/project/slurm-1001.out"]);
+        // a pending job has written nothing yet
+        assert!(logs("", "1005").is_empty());
+        let source = FixtureSource::new(Path::new(DEFAULT_FIXTURE_DIR), &LogConfig::default());
+        let log = source
+            .read_log("/home/jdoe/project/slurm-1001.out")
+            .unwrap();
+        assert!(log.starts_with("Loading dataset"));
+        assert!(source.read_log("/home/jdoe/project/missing.out").is_err());
+    }
+
+    #[test]
+    fn templates_come_after_the_job_patterns() {
+        let config = LogConfig {
+            templates: vec![
+                "slurm-%A_%a.out".into(),
+                "results/*/logs/%x-%A_%a.out".into(),
+            ],
+            ..LogConfig::default()
+        };
+        let sacct = read_fixture(&Path::new(DEFAULT_FIXTURE_DIR).join(SACCT_FILE)).unwrap();
+        let jobs = JobFields::from_sacct_str(&sacct).unwrap();
+        let job = jobs.iter().find(|job| job.job_id == "1010_1").unwrap();
+        assert_eq!(
+            LogSearch::new(&config).patterns(job, None),
+            ["slurm-%A_%a.out", "results/*/logs/%x-%A_%a.out"]
+        );
+    }
+
+    #[test]
+    fn templates_are_resolved_against_the_workdir() {
+        let config = "templates = [\"results/v1/logs/%x-%A_%a.out\"]\nfind_depth = 1";
+        assert_eq!(logs(config, "1010_1"), [EVALUATE_LOG]);
+        // the job's own patterns still win
+        assert_eq!(
+            logs(config, "1003_2"),
+            ["/home/jdoe/project/logs/sweep_1003_2.out"]
+        );
+    }
+
+    #[test]
+    fn find_depth_bounds_the_workdir_search() {
+        // the log is in the fourth level of `find -maxdepth`
+        assert!(logs("find_depth = 3", "1010_1").is_empty());
+        assert_eq!(logs("find_depth = 4", "1010_1"), [EVALUATE_LOG]);
+    }
+}
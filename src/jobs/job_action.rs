@@ -0,0 +1,231 @@
+use super::job_parser::{JobFields, JobState};
+use super::shell::shell_quote;
+
+/// Change asked on a job, run by the job source
+#[derive(Clone, Debug)]
+pub enum JobAction {
+    /// scancel, with an optional signal instead of killing the job
+    Cancel {
+        job_id: String,
+        signal: Option<String>,
+        pending_only: bool,
+    },
+    Scontrol {
+        command: ScontrolCommand,
+        job_id: String,
+    },
+    /// Shell command line submitting a job, run from `workdir`
+    Submit {
+        workdir: String,
+        command_line: String,
+    },
+}
+
+/// `scontrol` subcommands acting on a job
+#[derive(Clone, Copy, Debug)]
+pub enum ScontrolCommand {
+    Hold,
+    Release,
+    Requeue,
+    Suspend,
+    Resume,
+}
+
+impl ScontrolCommand {
+    pub fn all() -> [ScontrolCommand; 5] {
+        use ScontrolCommand::*;
+        [Hold, Release, Requeue, Suspend, Resume]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScontrolCommand::Hold => "hold",
+            ScontrolCommand::Release => "release",
+            ScontrolCommand::Requeue => "requeue",
+            ScontrolCommand::Suspend => "suspend",
+            ScontrolCommand::Resume => "resume",
+        }
+    }
+
+    /// Key selecting the command in the actions menu
+    pub fn key(&self) -> char {
+        match self {
+            ScontrolCommand::Hold => 'h',
+            ScontrolCommand::Release => 'e',
+            ScontrolCommand::Requeue => 'q',
+            ScontrolCommand::Suspend => 's',
+            ScontrolCommand::Resume => 'r',
+        }
+    }
+
+    /// Whether slurm accepts the command for the job, given its state and
+    /// the reason it is pending
+    pub fn allowed(&self, job: &JobFields) -> bool {
+        let state = &job.state;
+        // a held job stays pending, with a JobHeldUser or JobHeldAdmin reason
+        let held = job.reason.starts_with("JobHeld");
+        match self {
+            ScontrolCommand::Hold => matches!(state, JobState::Pending) && !held,
+            ScontrolCommand::Release => matches!(state, JobState::Pending) && held,
+            ScontrolCommand::Requeue => matches!(
+                state,
+                JobState::Running
+                    | JobState::Suspended
+                    | JobState::Completed
+                    | JobState::Failed(_)
+                    | JobState::Cancelled(_)
+            ),
+            ScontrolCommand::Suspend => matches!(state, JobState::Running),
+            ScontrolCommand::Resume => matches!(state, JobState::Suspended),
+        }
+    }
+}
+
+impl JobAction {
+    /// Slurm command doing the action, with its arguments
+    pub fn command(&self) -> (&'static str, Vec<String>) {
+        match self {
+            JobAction::Cancel {
+                job_id,
+                signal,
+                pending_only,
+            } => {
+                let mut args = vec![];
+                if let Some(signal) = signal {
+                    args.push(format!("--signal={}", signal));
+                }
+                if *pending_only {
+                    args.push("--state=PENDING".to_string());
+                }
+                args.push(job_id.clone());
+                ("scancel", args)
+            }
+            JobAction::Scontrol { command, job_id } => {
+                ("scontrol", vec![command.name().to_string(), job_id.clone()])
+            }
+            // the recorded line may use quotes or relative paths, so it goes
+            // through a shell in the directory it was first run from
+            JobAction::Submit {
+                workdir,
+                command_line,
+            } => {
+                let script = format!("cd {} && {}", shell_quote(workdir), command_line);
+                ("sh", vec!["-c".to_string(), script])
+            }
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            JobAction::Cancel {
+                job_id,
+                signal,
+                pending_only,
+            } => {
+                let what = if *pending_only {
+                    format!("pending tasks of {}", job_id)
+                } else {
+                    job_id.to_string()
+                };
+                match signal {
+                    Some(signal) => format!("Send {} to {}", signal, what),
+                    None => format!("Cancel {}", what),
+                }
+            }
+            JobAction::Scontrol { command, job_id } => {
+                let name = command.name();
+                format!("{}{} {}", name[..1].to_uppercase(), &name[1..], job_id)
+            }
+            JobAction::Submit { command_line, .. } => format!("Submit `{}`", command_line),
+        }
+    }
+}
+
+/// Id of the job submitted by sbatch, from its output `Submitted batch job 1234`
+pub fn submitted_job_id(output: &str) -> Option<String> {
+    let (_, rest) = output.split_once("Submitted batch job ")?;
+    let id: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    (!id.is_empty()).then_some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::fixture::DEFAULT_FIXTURE_DIR;
+
+    fn allowed(job_id: &str) -> Vec<&'static str> {
+        let sacct = std::fs::read_to_string(format!("{}/sacct.txt", DEFAULT_FIXTURE_DIR)).unwrap();
+        let jobs = JobFields::from_sacct_str(&sacct).unwrap();
+        let job = jobs.iter().find(|job| job.job_id == job_id).unwrap();
+        ScontrolCommand::all()
+            .into_iter()
+            .filter(|command| command.allowed(job))
+            .map(|command| command.name())
+            .collect()
+    }
+
+    #[test]
+    fn hold_or_release_by_reason() {
+        // pending on a dependency
+        assert_eq!(allowed("1005"), ["hold"]);
+        // held by the user
+        assert_eq!(allowed("1003_[6-8]"), ["release"]);
+        assert_eq!(allowed("1004"), ["requeue", "suspend"]);
+        assert_eq!(allowed("1001"), ["requeue"]);
+    }
+
+    #[test]
+    fn requeue_jobs_ended_by_the_cluster() {
+        let sacct = std::fs::read_to_string(format!("{}/sacct.txt", DEFAULT_FIXTURE_DIR)).unwrap();
+        let mut job = JobFields::from_sacct_str(&sacct).unwrap().remove(0);
+        for state in ["NODE_FAIL", "BOOT_FAIL", "PREEMPTED"] {
+            job.state = JobState::from_str(state);
+            assert!(ScontrolCommand::Requeue.allowed(&job), "{}", state);
+        }
+    }
+
+    #[test]
+    fn submitted_job_ids() {
+        assert_eq!(
+            submitted_job_id("Submitted batch job 1234\n").as_deref(),
+            Some("1234")
+        );
+        assert_eq!(
+            submitted_job_id("Submitted batch job 1234 on cluster c1").as_deref(),
+            Some("1234")
+        );
+        assert_eq!(submitted_job_id("sbatch: error: invalid partition"), None);
+    }
+
+    #[test]
+    fn cancel_arguments() {
+        let cancel = |signal: Option<&str>, pending_only| JobAction::Cancel {
+            job_id: "1003".to_string(),
+            signal: signal.map(|s| s.to_string()),
+            pending_only,
+        };
+        assert_eq!(
+            cancel(None, false).command(),
+            ("scancel", vec!["1003".to_string()])
+        );
+        assert_eq!(cancel(None, false).description(), "Cancel 1003");
+        let action = cancel(Some("USR1"), true);
+        assert_eq!(
+            action.command().1,
+            ["--signal=USR1", "--state=PENDING", "1003"]
+        );
+        assert_eq!(action.description(), "Send USR1 to pending tasks of 1003");
+    }
+
+    #[test]
+    fn submit_runs_from_the_workdir() {
+        let action = JobAction::Submit {
+            workdir: "/home/jdoe/my project".to_string(),
+            command_line: "sbatch --mem=4G run.sh".to_string(),
+        };
+        assert_eq!(
+            action.command().1,
+            ["-c", "cd '/home/jdoe/my project' && sbatch --mem=4G run.sh"]
+        );
+    }
+}
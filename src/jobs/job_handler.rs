@@ -1,17 +1,22 @@
+use super::job_action::JobAction;
+use super::job_id::{ArrayPart, JobId};
 use super::job_parser::JobFields;
 use super::job_parser::JobState;
+use super::job_source::{CommandRunner, JobDetails, TimeBound, TimeWindow};
+use super::log_page::{FileSearch, LogPage, PageRange};
+use super::log_paths;
+use super::log_paths::LogSearch;
 use crate::app::App;
 use crate::app::FetchTime;
 use crate::job_query_info::JobQueryInfo;
 use crate::job_query_info::JobTime;
 use crate::jobs::job_parser::NumberOrCol;
-use crate::parser::RunMode;
 use clap::ValueEnum;
-use color_eyre::eyre::Result;
-use std::process::Command;
+use color_eyre::eyre::{Report, Result};
+use std::cell::Cell;
 use tracing::info;
 
-static FORMAT_STR: &str = "--format=JobID,JobName,Partition,Account,AllocCPUS,State,ExitCode,SubmitLine%50,WorkDir%100,Submit%20,ReqMem,MaxRSS,ElapsedRaw,TimelimitRaw,TotalCPU";
+static FORMAT_STR: &str = "--format=JobID,JobName,Partition,Account,AllocCPUS,State,ExitCode,SubmitLine%50,WorkDir%100,Submit%20,ReqMem,MaxRSS,ElapsedRaw,TimelimitRaw,TotalCPU,Reason,JobIDRaw";
 // "JobIDRaw",
 // "JobID",
 // "State",
@@ -53,18 +58,55 @@ static FORMAT_STR: &str = "--format=JobID,JobName,Partition,Account,AllocCPUS,St
 // TRESUsageOutMinTask TRESUsageOutTot     UID                 User
 // UserCPU             WCKey               WCKeyID             WorkDir
 
-fn run_sacct(run_mode: RunMode, hours_before_now: u16) -> Result<String> {
-    let fmt_time = format!("now-{}hours", hours_before_now);
-    let sacct_args = vec![FORMAT_STR, "-P", "-S", &fmt_time];
-    run_command(run_mode, "sacct", &sacct_args)
+fn run_sacct(runner: &impl CommandRunner, window: &TimeWindow, json: bool) -> Result<String> {
+    let start = window.start.sacct_arg();
+    let mut sacct_args = if json {
+        vec!["--json", "-S", &start]
+    } else {
+        vec![FORMAT_STR, "-P", "-S", &start]
+    };
+    let end = window.end.map(|end| end.sacct_arg());
+    if let Some(ref end) = end {
+        sacct_args.extend(["-E", end]);
+    }
+    runner.run_command("sacct", &sacct_args)
+}
+
+/// Lists jobs with sacct, in the output format chosen by `sacct_format`.
+/// In auto mode, the format that worked is remembered for the next calls.
+pub fn list_jobs_sacct(
+    runner: &impl CommandRunner,
+    window: &TimeWindow,
+    sacct_format: &Cell<SacctFormat>,
+) -> Result<Vec<JobFields>> {
+    match sacct_format.get() {
+        SacctFormat::Text => JobFields::from_sacct_str(&run_sacct(runner, window, false)?),
+        SacctFormat::Json => JobFields::from_sacct_json(&run_sacct(runner, window, true)?),
+        SacctFormat::Auto => {
+            let json_res = run_sacct(runner, window, true)
+                .and_then(|sacct_res| JobFields::from_sacct_json(&sacct_res));
+            if let Ok(job_fields) = json_res {
+                sacct_format.set(SacctFormat::Json);
+                return Ok(job_fields);
+            }
+            // older sacct versions do not know about --json
+            info!("sacct --json failed, falling back to text output");
+            let job_fields = JobFields::from_sacct_str(&run_sacct(runner, window, false)?)?;
+            sacct_format.set(SacctFormat::Text);
+            Ok(job_fields)
+        }
+    }
 }
 
 fn update_max_rss(job_fields: &mut JobFields, all_job_fields: &[JobFields]) {
+    let Some(job_id) = job_fields.id() else {
+        return;
+    };
     job_fields.maxrss = NumberOrCol::Value(
         all_job_fields
             .iter()
             .filter_map(|f| {
-                if f.job_id.starts_with(&job_fields.job_id) {
+                if f.id().is_some_and(|id| id.belongs_to(&job_id)) {
                     info!("max rss: {:?}", f.maxrss);
                     Some(f.maxrss.clone().take().unwrap())
                 } else {
@@ -76,16 +118,37 @@ fn update_max_rss(job_fields: &mut JobFields, all_job_fields: &[JobFields]) {
     );
 }
 
-pub fn fetch_jobs(app: &App, job_info: JobQueryInfo) -> Result<Vec<JobFields>> {
-    let cli = &app.cli;
-    let hours_before_now = match app.fetch_time {
+pub fn time_window(fetch_time: FetchTime) -> TimeWindow {
+    let hours_before_now = match fetch_time {
         FetchTime::Today => 24,
         FetchTime::ThreeDaysAgo => 24 * 3,
         FetchTime::AWeekAgo => 24 * 7,
-        FetchTime::SpecificWindow { .. } => todo!(),
+        FetchTime::TwoWeeksAgo => 24 * 14,
+        FetchTime::AMonthAgo => 24 * 30,
+        FetchTime::ThreeMonthsAgo => 24 * 91,
+        FetchTime::SpecificWindow { start, end } => return TimeWindow { start, end },
     };
-    let sacct_res = run_sacct(cli.run_mode, hours_before_now)?;
-    let all_job_fields = JobFields::from_sacct_str(&sacct_res)?;
+    TimeWindow {
+        start: TimeBound::HoursAgo(hours_before_now),
+        end: None,
+    }
+}
+
+/// Rows of the job steps, which have no partition
+pub fn job_steps(all_job_fields: &[JobFields]) -> Vec<JobFields> {
+    all_job_fields
+        .iter()
+        .filter(|job_fields| job_fields.partition.is_empty() && job_fields.is_step())
+        .cloned()
+        .collect()
+}
+
+/// Keeps the jobs to display out of every sacct row, in display order
+pub fn filter_jobs(
+    app: &App,
+    job_info: &JobQueryInfo,
+    all_job_fields: Vec<JobFields>,
+) -> Vec<JobFields> {
     // remove fields with empty partition
     let mut job_fields_with_partition = all_job_fields.clone();
     job_fields_with_partition.retain(|job_fields| !job_fields.partition.is_empty());
@@ -108,51 +171,402 @@ pub fn fetch_jobs(app: &App, job_info: JobQueryInfo) -> Result<Vec<JobFields>> {
     }
     job_fields_with_partition[1..].sort_by(|f1, f2| f1.submit.cmp(&f2.submit).reverse());
     let capped = false;
-    let job_fields = if capped {
+    if capped {
         job_fields_with_partition
             .into_iter()
             .take(app.cli.job_max_display as usize)
             .collect()
     } else {
         job_fields_with_partition
+    }
+}
+
+/// Part of the job id expected in the name of its log files
+pub fn log_name_fragment(job_id: &str) -> String {
+    // steps write to the log of their job, and het components to the log of
+    // the het job
+    match JobId::parse(job_id) {
+        Some(id) => match id.array {
+            Some(ArrayPart::Task(task)) => format!("{}_{}", id.base, task),
+            Some(ArrayPart::Range { .. }) => format!("{}_", id.base),
+            None => id.base.to_string(),
+        },
+        None => job_id.to_string(),
+    }
+}
+
+/// Whether the file name holds the id fragment of the job on its own, not as
+/// part of a longer id like `1003_21` or `11003_2` for `1003_2`
+pub fn names_job(file_name: &str, fragment: &str) -> bool {
+    let ends_in_digit = fragment.ends_with(|c: char| c.is_ascii_digit());
+    file_name.match_indices(fragment).any(|(start, _)| {
+        let before = file_name[..start].chars().next_back();
+        let after = file_name[start + fragment.len()..].chars().next();
+        let digit_before = before.is_some_and(|c| c.is_ascii_digit());
+        let digit_after = ends_in_digit && after.is_some_and(|c| c.is_ascii_digit());
+        !digit_before && !digit_after
+    })
+}
+
+/// Log files of the job: where slurmctld says a running job writes, else the
+/// `--output` and `--error` patterns of the job and the configured templates,
+/// else a search of the workdir
+pub fn list_logs(
+    runner: &impl CommandRunner,
+    job: &JobFields,
+    search: &LogSearch,
+) -> Result<Vec<String>> {
+    if matches!(job.state, JobState::Running | JobState::Suspended) {
+        let logs = scontrol_logs(runner, job);
+        if !logs.is_empty() {
+            return Ok(logs);
+        }
+    }
+    let script = log_paths::script_path(job).and_then(|path| read_file(runner, &path).ok());
+    let patterns = search.patterns(job, script.as_deref());
+    let user = match log_paths::needs_user(&patterns) {
+        true => runner
+            .run_checked("id", &["-un"])
+            .ok()
+            .map(|user| user.trim().to_string()),
+        false => None,
+    };
+    let node = match log_paths::needs_node(&patterns) {
+        true => first_node(runner, &job.job_id),
+        false => None,
     };
-    Ok(job_fields)
+    let paths: Vec<String> = patterns
+        .iter()
+        .flat_map(|pattern| log_paths::expand(pattern, job, user.as_deref(), node.as_deref()))
+        .collect();
+    info!("log paths of {}: {:?}", job.job_id, paths);
+    let logs = existing_files(runner, &paths)?;
+    // files named after a pending job belong to other jobs, like the other
+    // tasks of its array
+    if !logs.is_empty() || job.state == JobState::Pending {
+        return Ok(logs);
+    }
+    get_log_files_finished_job(runner, &job.workdir, &job.job_id, search.find_depth)
+}
+
+/// StdOut and StdErr of `scontrol show job`, already expanded by slurm
+fn scontrol_logs(runner: &impl CommandRunner, job: &JobFields) -> Vec<String> {
+    let Some(id) = job.id() else {
+        return vec![];
+    };
+    let job_id = id.job().to_string();
+    match runner.run_checked("scontrol", &["show", "job", &job_id]) {
+        Ok(scontrol_res) => {
+            let fields = scontrol_fields(&scontrol_res);
+            let mut logs: Vec<String> = vec![];
+            for wanted in ["StdOut", "StdErr"] {
+                let value = fields.iter().find(|(name, _)| name == wanted);
+                if let Some((_, value)) = value {
+                    if value != "/dev/null" && !logs.contains(value) {
+                        logs.push(value.clone());
+                    }
+                }
+            }
+            logs
+        }
+        Err(e) => {
+            info!("no scontrol logs for {}: {}", job_id, e);
+            vec![]
+        }
+    }
+}
+
+/// First node the job ran on, which `%N` stands for
+fn first_node(runner: &impl CommandRunner, job_id: &str) -> Option<String> {
+    let job_id = JobId::parse(job_id).map_or(job_id.to_string(), |id| id.target().to_string());
+    let node_list = runner
+        .run_checked(
+            "sacct",
+            &["-j", &job_id, "-X", "-n", "-P", "-o", "NodeList"],
+        )
+        .ok()?;
+    log_paths::first_node(node_list.lines().next()?)
+}
+
+/// Files matching the globs of `log_paths::expand`, `*` matching any task id
+fn existing_files(runner: &impl CommandRunner, paths: &[String]) -> Result<Vec<String>> {
+    if paths.is_empty() {
+        return Ok(vec![]);
+    }
+    // the shell expands the globs, left unquoted on purpose, without
+    // splitting the paths holding spaces
+    let mut args = vec![
+        "-c",
+        r#"IFS=; for p in "$@"; do for f in $p; do [ -f "$f" ] && echo "$f"; done; done; true"#,
+        "sh",
+    ];
+    args.extend(paths.iter().map(|p| p.as_str()));
+    Ok(find_lines(&runner.run_command("sh", &args)?))
 }
 
 pub fn get_log_files_finished_job(
-    run_mode: RunMode,
+    runner: &impl CommandRunner,
     workdir: &str,
     job_id: &str,
-) -> Result<String> {
-    let regex_id = if job_id.contains('[') {
-        job_id.split('[').next().unwrap().to_string() + "_*"
+    depth: usize,
+) -> Result<Vec<String>> {
+    let fragment = log_name_fragment(job_id);
+    let regex = String::from("*") + &fragment + "*";
+    let depth = depth.to_string();
+    let find_args = [workdir, "-maxdepth", &depth, "-name", &regex];
+    let find_result = runner.run_command("find", &find_args)?;
+    let logs = find_lines(&find_result)
+        .into_iter()
+        .filter(|path| {
+            let file_name = path.rsplit('/').next().unwrap_or(path);
+            names_job(file_name, &fragment)
+        })
+        .collect();
+    Ok(logs)
+}
+
+static SCRIPT_EXTENSIONS: [&str; 2] = [".sh", ".sbatch"];
+
+pub fn is_batch_script(file_name: &str) -> bool {
+    SCRIPT_EXTENSIONS.iter().any(|ext| file_name.ends_with(ext))
+}
+
+/// Batch scripts in `dir` and its subdirectories
+pub fn find_scripts(runner: &impl CommandRunner, dir: &str) -> Result<Vec<String>> {
+    let find_args = [
+        dir,
+        "-maxdepth",
+        "2",
+        "-type",
+        "f",
+        "(",
+        "-name",
+        "*.sh",
+        "-o",
+        "-name",
+        "*.sbatch",
+        ")",
+    ];
+    let mut scripts = find_lines(&runner.run_command("find", &find_args)?);
+    scripts.sort();
+    Ok(scripts)
+}
+
+/// Paths printed by find, one per line
+fn find_lines(find_result: &str) -> Vec<String> {
+    if !find_result.is_empty() {
+        find_result
+            .trim_end_matches('\n')
+            .split('\n')
+            .map(|s| s.to_string())
+            .collect()
     } else {
-        job_id.to_string()
-    };
-    let regex = String::from("*") + &regex_id + "*";
-    let find_args = [workdir, "-maxdepth", "2", "-name", &regex];
-    run_command(run_mode, "find", &find_args)
+        vec![]
+    }
+}
+
+pub fn run_action(runner: &impl CommandRunner, action: &JobAction) -> Result<String> {
+    let (cmd, args) = action.command();
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    let output = runner.run_checked(cmd, &args)?;
+    Ok(match output.trim() {
+        "" => format!("{}: done", action.description()),
+        out => format!("{}: {}", action.description(), out),
+    })
 }
 
-pub fn read_file(run_mode: RunMode, path: &str) -> Result<String> {
+static DETAILS_FORMAT: &str = "--format=JobID,JobName,User,Account,Partition,QOS,State,Reason,ExitCode,DerivedExitCode,Submit,Eligible,Start,End,Elapsed,Timelimit,Priority,NNodes,NCPUS,NTasks,NodeList,ReqMem,ReqTRES,AllocTRES,MaxRSS,AveRSS,TotalCPU,CPUTime,Constraints,Reservation,Comment,WorkDir,SubmitLine";
+
+/// Fields from sacct, completed by scontrol while slurmctld still knows the job
+pub fn job_details(runner: &impl CommandRunner, job_id: &str) -> Result<JobDetails> {
+    let sacct_res = runner.run_checked("sacct", &["-j", job_id, DETAILS_FORMAT, "-P"])?;
+    let mut details = sacct_row_details(&sacct_res, job_id)?;
+    // finished jobs are forgotten by slurmctld after MinJobAge
+    match runner.run_checked("scontrol", &["show", "job", "-d", job_id]) {
+        Ok(scontrol_res) => {
+            for (name, value) in scontrol_fields(&scontrol_res) {
+                if !details
+                    .iter()
+                    .any(|(known, _)| known.eq_ignore_ascii_case(&name))
+                {
+                    details.push((name, value));
+                }
+            }
+        }
+        Err(e) => info!("no scontrol details for {}: {}", job_id, e),
+    }
+    Ok(details)
+}
+
+/// Fields of the first row of `sacct -P` for the job, named by the header
+pub fn sacct_row_details(sacct_res: &str, job_id: &str) -> Result<JobDetails> {
+    let mut lines = sacct_res.lines();
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or(Report::msg("Empty sacct output"))?
+        .split('|')
+        .collect();
+    let wanted = JobId::parse(job_id);
+    let row = lines
+        .map(|line| line.split('|').collect::<Vec<_>>())
+        .find(|row| {
+            let Some(id) = JobId::parse(row[0]) else {
+                return false;
+            };
+            // an array or heterogeneous job is shown by its first component
+            wanted.as_ref().is_some_and(|wanted| {
+                id == *wanted
+                    || (wanted.is_plain()
+                        && id.base == wanted.base
+                        && !id.is_step()
+                        && (id.array.is_some() || id.het_offset == Some(0)))
+            })
+        })
+        .ok_or(Report::msg(format!(
+            "No accounting data for job {}",
+            job_id
+        )))?;
+    Ok(header
+        .iter()
+        .zip(row)
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect())
+}
+
+/// `Name=value` pairs of the first job shown by `scontrol show job`
+fn scontrol_fields(scontrol_res: &str) -> JobDetails {
+    let first_job = scontrol_res.trim().split("\n\n").next().unwrap_or("");
+    let mut fields: JobDetails = vec![];
+    for token in first_job.split_whitespace() {
+        match token.split_once('=') {
+            Some((name, value)) if !name.is_empty() && !name.contains(',') => {
+                fields.push((name.to_string(), value.to_string()))
+            }
+            // values may contain spaces
+            _ => {
+                if let Some((_, value)) = fields.last_mut() {
+                    value.push(' ');
+                    value.push_str(token);
+                }
+            }
+        }
+    }
+    fields
+}
+
+pub fn read_file(runner: &impl CommandRunner, path: &str) -> Result<String> {
     let cat_args = vec![path];
-    run_command(run_mode, "cat", &cat_args)
-}
-
-fn run_command(run_mode: RunMode, cmd: &str, command_args: &[&str]) -> Result<String> {
-    let output_jobs = match run_mode {
-        RunMode::Slurm => Command::new(cmd).args(command_args).output()?,
-        RunMode::FromFile => Command::new("/bin/cat")
-            .arg("test_data/10_random_users.txt")
-            .output()?,
-        RunMode::Ssh => Command::new("ssh")
-            .arg("maestro")
-            .arg(cmd)
-            .args(command_args)
-            .output()?,
+    runner.run_command("cat", &cat_args)
+}
+
+/// Reads the size of the file, then a page of it with `tail -c | head -c` so
+/// that only the page goes through ssh. The size comes first on its own line,
+/// with the offset the page starts at.
+static READ_PAGE_SCRIPT: &str = r#"size=$(wc -c < "$1") || exit 1
+start=$2
+[ "$start" = tail ] && start=$(( size > $3 ? size - $3 : 0 ))
+echo $size $start
+tail -c +$((start + 1)) "$1" | head -c "$3""#;
+
+pub fn read_file_page(
+    runner: &impl CommandRunner,
+    path: &str,
+    range: PageRange,
+) -> Result<LogPage> {
+    // the start of the tail depends on the size, read by the same command
+    let start = match range {
+        PageRange::Tail => "tail".to_string(),
+        _ => range.start(0).to_string(),
+    };
+    let len = range.read_len().to_string();
+    let args = ["-c", READ_PAGE_SCRIPT, "sh", path, &start, &len];
+    let output = runner.output("sh", &args)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Report::msg(format!(
+            "Could not read {}: {}",
+            path,
+            stderr.trim()
+        )));
+    }
+    let stdout = &output.stdout;
+    let header_end = stdout
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or(Report::msg(format!("Could not read {}", path)))?;
+    let header = String::from_utf8_lossy(&stdout[..header_end]);
+    let numbers: Vec<u64> = header
+        .split_whitespace()
+        .filter_map(|n| n.parse().ok())
+        .collect();
+    let [file_size, start] = numbers[..] else {
+        return Err(Report::msg(format!("Could not read {}: {}", path, header)));
     };
-    let out_txt = String::from_utf8(output_jobs.stdout)?;
-    Ok(out_txt)
+    let bytes = &stdout[header_end + 1..];
+    Ok(LogPage::from_bytes(range, bytes, start, file_size))
+}
+
+/// Prints the offset of the line of the next match with `grep -b`, so that
+/// only the offset goes through ssh. Perl regexes are the closest to the ones
+/// of the viewer, extended ones being used when grep lacks them.
+static SEARCH_FILE_SCRIPT: &str = r#"path=$1 pattern=$2 offset=$3 case=$5
+syntax=-E
+grep -P x </dev/null 2>/dev/null
+[ $? -eq 1 ] && syntax=-P
+matches() { grep -a -b $syntax $case -e "$pattern" "$@" | cut -d: -f1; }
+if [ "$4" = forward ]; then
+    found=$(tail -c +$((offset + 1)) "$path" | matches -m 1)
+    [ -n "$found" ] && echo $((offset + found)) || matches -m 1 "$path"
+else
+    found=$(head -c "$offset" "$path" | matches | tail -n 1)
+    [ -n "$found" ] && echo "$found" || matches "$path" | tail -n 1
+fi"#;
+
+/// Searches the file from the offset, wrapping around its end
+pub fn search_file(
+    runner: &impl CommandRunner,
+    path: &str,
+    search: &FileSearch,
+) -> Result<Option<u64>> {
+    let offset = search.offset.to_string();
+    let direction = match search.backward {
+        true => "backward",
+        false => "forward",
+    };
+    let case = match search.ignore_case {
+        true => "-i",
+        false => "",
+    };
+    let args = [
+        "-c",
+        SEARCH_FILE_SCRIPT,
+        "sh",
+        path,
+        &search.pattern,
+        &offset,
+        direction,
+        case,
+    ];
+    let output = runner.output("sh", &args)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // grep fails inside a pipeline, so its errors only show on stderr
+    if stdout.trim().is_empty() && !stderr.trim().is_empty() {
+        return Err(Report::msg(format!(
+            "Could not search {}: {}",
+            path,
+            stderr.trim()
+        )));
+    }
+    Ok(stdout.trim().parse().ok())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum SacctFormat {
+    /// Use json when sacct supports it
+    Auto,
+    Json,
+    Text,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -160,3 +574,109 @@ pub enum DisplayMode {
     Cpu,
     Ram,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Output};
+
+    /// Runs commands on the local machine
+    struct Local;
+
+    impl CommandRunner for Local {
+        fn output(&self, cmd: &str, command_args: &[&str]) -> Result<Output> {
+            Ok(Command::new(cmd).args(command_args).output()?)
+        }
+    }
+
+    /// Finds nothing, recording the commands it is given
+    struct Recorder {
+        calls: std::cell::RefCell<Vec<String>>,
+    }
+
+    impl CommandRunner for Recorder {
+        fn output(&self, cmd: &str, command_args: &[&str]) -> Result<Output> {
+            let call = format!("{} {}", cmd, command_args.join(" "));
+            self.calls.borrow_mut().push(call);
+            Ok(Command::new("true").output()?)
+        }
+    }
+
+    #[test]
+    fn find_depth_reaches_find() {
+        let sacct = std::fs::read_to_string("test_data/fixture/sacct.txt").unwrap();
+        let jobs = JobFields::from_sacct_str(&sacct).unwrap();
+        let job = jobs.iter().find(|job| job.job_id == "1010_1").unwrap();
+        let config = toml::from_str("find_depth = 4").unwrap();
+        let recorder = Recorder {
+            calls: Default::default(),
+        };
+        assert!(list_logs(&recorder, job, &LogSearch::new(&config))
+            .unwrap()
+            .is_empty());
+        let calls = recorder.calls.borrow();
+        assert_eq!(
+            calls.last().unwrap(),
+            "find /home/jdoe/evaluation -maxdepth 4 -name *1010_1*"
+        );
+    }
+
+    #[test]
+    fn details_of_the_sacct_row_and_scontrol() {
+        let sacct = "JobID|State|NodeList\n\
+            1003_1|COMPLETED|node01\n\
+            1003_1.batch|COMPLETED|node01\n\
+            1003_2|FAILED|node02\n";
+        let details = sacct_row_details(sacct, "1003_2").unwrap();
+        assert_eq!(details[1], ("State".to_string(), "FAILED".to_string()));
+        // the array is shown by its first task
+        let details = sacct_row_details(sacct, "1003").unwrap();
+        assert_eq!(details[0].1, "1003_1");
+        assert!(sacct_row_details(sacct, "1004").is_err());
+        let scontrol = "JobId=1004 JobName=assemble
+   Command=/home/jdoe/run.sh --fast
+   Reason=None Dependency=(null)
+
+JobId=1005 JobName=other
+";
+        assert_eq!(
+            scontrol_fields(scontrol),
+            [
+                ("JobId", "1004"),
+                ("JobName", "assemble"),
+                ("Command", "/home/jdoe/run.sh --fast"),
+                ("Reason", "None"),
+                ("Dependency", "(null)")
+            ]
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn log_names_hold_the_whole_id() {
+        assert!(names_job("slurm-1003_2.out", "1003_2"));
+        assert!(names_job("run_1003_2-err.log", "1003_2"));
+        assert!(!names_job("slurm-1003_21.out", "1003_2"));
+        assert!(!names_job("slurm-1003_200.out", "1003_2"));
+        assert!(!names_job("slurm-11003_2.out", "1003_2"));
+        assert!(names_job("slurm-1003_21.out", "1003_"));
+        assert!(!names_job("slurm-10030.out", "1003"));
+    }
+
+    #[test]
+    fn finished_job_logs_leave_out_other_tasks() {
+        let dir = std::env::temp_dir().join(format!("sboard-logs-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        for name in [
+            "slurm-1003_2.out",
+            "slurm-1003_21.out",
+            "slurm-1003_200.out",
+        ] {
+            std::fs::write(dir.join("out").join(name), "").unwrap();
+        }
+        let workdir = dir.display().to_string();
+        let logs = get_log_files_finished_job(&Local, &workdir, "1003_2", 2).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(logs, [format!("{}/out/slurm-1003_2.out", workdir)]);
+    }
+}
@@ -0,0 +1,363 @@
+//! Job ids as printed by sacct: `1234`, `1234.batch`, `12_3`, `12_[4-9%2]`,
+//! `1234+1` or `1234+1.0`
+use std::fmt;
+
+/// Id of a job, array task, het component, or of one of their steps. Ids sort
+/// by job, a job coming before its tasks, components and steps.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId {
+    pub base: u64,
+    pub array: Option<ArrayPart>,
+    pub het_offset: Option<u32>,
+    pub step: Option<StepName>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ArrayPart {
+    Task(u32),
+    /// Tasks not started yet, like `[4-9%2]`, at most `throttle` running at once
+    Range {
+        tasks: Vec<u32>,
+        throttle: Option<u32>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StepName {
+    Batch,
+    Extern,
+    Interactive,
+    Numbered(u32),
+    Other(String),
+}
+
+impl StepName {
+    fn from_str(s: &str) -> Self {
+        match s {
+            "batch" => StepName::Batch,
+            "extern" => StepName::Extern,
+            "interactive" => StepName::Interactive,
+            _ => match s.parse() {
+                Ok(number) => StepName::Numbered(number),
+                Err(_) => StepName::Other(s.to_string()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for StepName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StepName::Batch => write!(f, "batch"),
+            StepName::Extern => write!(f, "extern"),
+            StepName::Interactive => write!(f, "interactive"),
+            StepName::Numbered(number) => write!(f, "{}", number),
+            StepName::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl JobId {
+    pub fn parse(s: &str) -> Option<JobId> {
+        let (job, step) = match s.split_once('.') {
+            Some((job, step)) => (job, Some(StepName::from_str(step))),
+            None => (s, None),
+        };
+        let (job, het_offset) = match job.split_once('+') {
+            Some((job, offset)) => (job, Some(offset.parse().ok()?)),
+            None => (job, None),
+        };
+        let (base, array) = match job.split_once('_') {
+            Some((base, tasks)) => (base, Some(ArrayPart::parse(tasks)?)),
+            None => (job, None),
+        };
+        Some(JobId {
+            base: base.parse().ok()?,
+            array,
+            het_offset,
+            step,
+        })
+    }
+
+    /// The job the step belongs to, itself for a job
+    pub fn job(&self) -> JobId {
+        JobId {
+            step: None,
+            ..self.clone()
+        }
+    }
+
+    /// Job outside of any array or het job, and not a step
+    pub fn is_plain(&self) -> bool {
+        self.array.is_none() && self.het_offset.is_none() && self.step.is_none()
+    }
+
+    pub fn is_step(&self) -> bool {
+        self.step.is_some()
+    }
+
+    /// Single task of an array
+    pub fn is_array_task(&self) -> bool {
+        matches!(self.array, Some(ArrayPart::Task(_)))
+    }
+
+    pub fn is_het_component(&self) -> bool {
+        self.het_offset.is_some()
+    }
+
+    /// Several tasks of an array, like its pending tasks `12_[4-9]`, or the
+    /// whole array of a collapsed row
+    pub fn is_task_range(&self) -> bool {
+        matches!(self.array, Some(ArrayPart::Range { .. }))
+    }
+
+    /// Id slurm acts on for this one: the job of a step, or the whole array
+    /// for a range of its tasks
+    pub fn target(&self) -> JobId {
+        match self.array {
+            Some(ArrayPart::Range { .. }) => JobId {
+                base: self.base,
+                array: None,
+                het_offset: None,
+                step: None,
+            },
+            _ => self.job(),
+        }
+    }
+
+    /// Array job id, for a task or the pending tasks of an array
+    pub fn array_id(&self) -> Option<u64> {
+        self.array.as_ref().map(|_| self.base)
+    }
+
+    /// Het job id, for a het component
+    pub fn het_id(&self) -> Option<u64> {
+        self.het_offset.map(|_| self.base)
+    }
+
+    /// Ids of the array tasks, none for a job outside an array
+    pub fn task_ids(&self) -> Vec<u32> {
+        match &self.array {
+            Some(ArrayPart::Task(task)) => vec![*task],
+            Some(ArrayPart::Range { tasks, .. }) => tasks.clone(),
+            None => vec![],
+        }
+    }
+
+    /// Whether this is a step of `job`, or `job` itself. A step of `1234` is
+    /// not one of `123`, nor of the het component `1234+1`.
+    pub fn belongs_to(&self, job: &JobId) -> bool {
+        self.job() == *job
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.base)?;
+        match &self.array {
+            Some(ArrayPart::Task(task)) => write!(f, "_{}", task)?,
+            Some(ArrayPart::Range { tasks, throttle }) => {
+                write!(f, "_[{}", format_ranges(tasks))?;
+                if let Some(throttle) = throttle {
+                    write!(f, "%{}", throttle)?;
+                }
+                write!(f, "]")?;
+            }
+            None => (),
+        }
+        if let Some(offset) = self.het_offset {
+            write!(f, "+{}", offset)?;
+        }
+        if let Some(step) = &self.step {
+            write!(f, ".{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+impl ArrayPart {
+    /// `3` for a task, or `[4-9%2]` for the tasks still pending
+    fn parse(s: &str) -> Option<ArrayPart> {
+        match s.strip_prefix('[') {
+            Some(ranges) => {
+                let ranges = ranges.trim_end_matches(']');
+                let throttle = match ranges.split_once('%') {
+                    Some((_, throttle)) => Some(throttle.parse().ok()?),
+                    None => None,
+                };
+                Some(ArrayPart::Range {
+                    tasks: parse_ranges(ranges),
+                    throttle,
+                })
+            }
+            None => Some(ArrayPart::Task(s.parse().ok()?)),
+        }
+    }
+}
+
+/// Task ids of an array spec like `1-5,7,9-15:2%4`
+pub fn parse_ranges(spec: &str) -> Vec<u32> {
+    // the throttle after % limits running tasks, not the ids
+    let spec = spec.split('%').next().unwrap_or(spec);
+    spec.split(',')
+        .flat_map(|range| {
+            let (range, step) = match range.split_once(':') {
+                Some((range, step)) => (range, step.parse().unwrap_or(1)),
+                None => (range, 1),
+            };
+            let (first, last) = range.split_once('-').unwrap_or((range, range));
+            match (first.trim().parse::<u32>(), last.trim().parse::<u32>()) {
+                (Ok(first), Ok(last)) => (first..=last).step_by(step.max(1)).collect(),
+                _ => vec![],
+            }
+        })
+        .collect()
+}
+
+/// Shortest spec for the task ids, like `1-5,7,9-12`
+pub fn format_ranges(task_ids: &[u32]) -> String {
+    let mut ids = task_ids.to_vec();
+    ids.sort();
+    ids.dedup();
+    let mut ranges: Vec<(u32, u32)> = vec![];
+    for id in ids {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == id => *last = id,
+            _ => ranges.push((id, id)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(first, last)| match last - first {
+            0 => first.to_string(),
+            _ => format!("{}-{}", first, last),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> JobId {
+        JobId::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for s in [
+            "1234",
+            "1234.batch",
+            "1234.extern",
+            "1234.0",
+            "12_3",
+            "12_3.batch",
+            "12_[4-9]",
+            "12_[4-9%2]",
+            "12_[1-3,7]",
+            "1234+1",
+            "1234+1.0",
+        ] {
+            assert_eq!(id(s).to_string(), s);
+        }
+        assert_eq!(JobId::parse("JobID"), None);
+        assert_eq!(JobId::parse("12_x"), None);
+    }
+
+    #[test]
+    fn parts_of_the_id() {
+        let task = id("12_3.batch");
+        assert_eq!(task.base, 12);
+        assert_eq!(task.array, Some(ArrayPart::Task(3)));
+        assert_eq!(task.step, Some(StepName::Batch));
+        assert_eq!(task.job(), id("12_3"));
+        let pending = id("12_[4-6%2]");
+        assert_eq!(
+            pending.array,
+            Some(ArrayPart::Range {
+                tasks: vec![4, 5, 6],
+                throttle: Some(2),
+            })
+        );
+        let component = id("1234+1.0");
+        assert_eq!(component.het_id(), Some(1234));
+        assert_eq!(component.het_offset, Some(1));
+        assert_eq!(component.step, Some(StepName::Numbered(0)));
+        assert_eq!(
+            id("1234.step_x").step,
+            Some(StepName::Other("step_x".into()))
+        );
+    }
+
+    #[test]
+    fn steps_belong_to_their_job_only() {
+        assert!(id("1234.batch").belongs_to(&id("1234")));
+        assert!(id("1234").belongs_to(&id("1234")));
+        assert!(!id("1234.batch").belongs_to(&id("123")));
+        assert!(!id("1234").belongs_to(&id("123")));
+        assert!(!id("1234.batch").belongs_to(&id("1234+1")));
+        assert!(id("1234+1.0").belongs_to(&id("1234+1")));
+        assert!(!id("1234+1.0").belongs_to(&id("1234")));
+        assert!(id("12_3.batch").belongs_to(&id("12_3")));
+        assert!(!id("12_3.batch").belongs_to(&id("12_4")));
+    }
+
+    #[test]
+    fn actions_target_the_job_or_whole_array() {
+        assert_eq!(id("1234.batch").target(), id("1234"));
+        assert_eq!(id("12_3.0").target(), id("12_3"));
+        assert_eq!(id("12_[4-9%2]").target(), id("12"));
+        assert_eq!(id("1234+1.0").target(), id("1234+1"));
+        assert!(id("12_[4-9]").is_task_range());
+        assert!(!id("12_4").is_task_range());
+        assert!(!id("12").is_task_range());
+    }
+
+    #[test]
+    fn jobs_sort_before_their_tasks_components_and_steps() {
+        let mut ids: Vec<JobId> = [
+            "10",
+            "9.batch",
+            "12_3.batch",
+            "12+1",
+            "12_3",
+            "12.extern",
+            "9",
+            "12.batch",
+            "12",
+        ]
+        .into_iter()
+        .map(id)
+        .collect();
+        ids.sort();
+        let sorted: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        assert_eq!(
+            sorted,
+            [
+                "9",
+                "9.batch",
+                "10",
+                "12",
+                "12.batch",
+                "12.extern",
+                "12+1",
+                "12_3",
+                "12_3.batch"
+            ]
+        );
+    }
+
+    #[test]
+    fn task_ranges() {
+        assert_eq!(parse_ranges("1-3,7"), [1, 2, 3, 7]);
+        assert_eq!(parse_ranges("1-10:3"), [1, 4, 7, 10]);
+        assert_eq!(parse_ranges("0-4:2%1"), [0, 2, 4]);
+        assert_eq!(parse_ranges("5%2"), [5]);
+        assert_eq!(parse_ranges("x"), Vec::<u32>::new());
+        assert_eq!(format_ranges(&[7, 1, 2, 3, 9, 10, 3]), "1-3,7,9-10");
+        assert_eq!(format_ranges(&parse_ranges("1-10:3")), "1,4,7,10");
+        // a step is written out, the throttle kept
+        assert_eq!(id("12_[1-7:3%2]").to_string(), "12_[1,4,7%2]");
+    }
+}
@@ -6,9 +6,11 @@ use color_eyre::Result;
 use phf::phf_map;
 use ratatui::prelude::Color;
 use std::default::Default;
+use std::fmt;
 use tracing::info;
 
-use crate::{jobs::job_handler, parser::RunMode, ui::Colorable};
+use super::job_id::JobId;
+use crate::ui::Colorable;
 
 static SACCT_MAP: phf::Map<&'static str, usize> = phf_map! {
     "JobID" => 0,
@@ -26,6 +28,8 @@ static SACCT_MAP: phf::Map<&'static str, usize> = phf_map! {
     "ElapsedRaw" => 12,
     "TimelimitRaw" => 13,
     "TotalCPU" => 14,
+    "Reason" => 15,
+    "JobIDRaw" => 16,
 };
 
 #[derive(Clone, Debug)]
@@ -45,23 +49,32 @@ pub struct JobFields {
     pub elapsed: NumberOrCol,
     pub time_limit: NumberOrCol,
     pub cpu_time_raw: NumberOrCol,
+    /// Why the job is pending, like `Priority` or `JobHeldUser`, `None` once
+    /// it runs
+    pub reason: String,
+    /// Id slurm gave the job, an array task having its own, like `1250` for
+    /// `1003_2`
+    pub job_id_raw: String,
+    /// Task or component count by state, for a row standing for a whole array
+    /// or het job
+    pub group_summary: Option<String>,
 }
 
 // from sacct doc
-// BF BOOT_FAIL => Unknown
+// BF BOOT_FAIL => Failed
 // CA CANCELLED => Cancelled
 // CD COMPLETED => Completed
 // DL DEADLINE => Timeout
 // F FAILED => Failed
-// NF NODE_FAIL => Unknown
+// NF NODE_FAIL => Failed
 // OOM OUT_OF_MEMORY => OutOfMemory
 // PD PENDING => Pending
-// PR PREEMPTED => Unknown
+// PR PREEMPTED => Failed
 // R RUNNING => Running
 // RQ REQUEUED => Unknown
 // RS RESIZING => Unknown
 // RV REVOKED => Unknown
-// S SUSPENDED => Unknown
+// S SUSPENDED => Suspended
 // TO TIMEOUT => Timeout
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -70,13 +83,15 @@ pub enum JobState {
     Completed,
     Cancelled(String),
     Pending,
-    Failed,
+    Suspended,
+    /// FAILED, or ended by the cluster: NODE_FAIL, BOOT_FAIL, PREEMPTED
+    Failed(String),
     Header,
     Unknown(String),
 }
 
 impl JobState {
-    fn from_str(s: &str) -> Self {
+    pub fn from_str(s: &str) -> Self {
         let state_str = s.split_whitespace().next().unwrap_or("");
         match state_str {
             "COMPLETED" => JobState::Completed,
@@ -85,23 +100,71 @@ impl JobState {
             }
             "PENDING" => JobState::Pending,
             "RUNNING" => JobState::Running,
-            "FAILED" => JobState::Failed,
+            "SUSPENDED" => JobState::Suspended,
+            "FAILED" | "NODE_FAIL" | "BOOT_FAIL" | "PREEMPTED" => JobState::Failed(s.to_string()),
             "State" => JobState::Header,
             _ => JobState::Unknown(s.to_string()),
         }
     }
 }
 
-impl ToString for JobState {
-    fn to_string(&self) -> String {
+impl JobState {
+    /// Abbreviation used by squeue, like CD for COMPLETED
+    pub fn short_name(&self) -> String {
+        let name = match self {
+            JobState::Cancelled(s) | JobState::Failed(s) | JobState::Unknown(s) => {
+                s.split_whitespace().next().unwrap_or("")
+            }
+            JobState::Completed => "COMPLETED",
+            JobState::Running => "RUNNING",
+            JobState::Pending => "PENDING",
+            JobState::Suspended => "SUSPENDED",
+            JobState::Header => "State",
+        };
+        match name {
+            "BOOT_FAIL" => "BF",
+            "CANCELLED" => "CA",
+            "COMPLETED" => "CD",
+            "DEADLINE" => "DL",
+            "FAILED" => "F",
+            "NODE_FAIL" => "NF",
+            "OUT_OF_MEMORY" => "OOM",
+            "PENDING" => "PD",
+            "PREEMPTED" => "PR",
+            "RUNNING" => "R",
+            "REQUEUED" => "RQ",
+            "SUSPENDED" => "S",
+            "TIMEOUT" => "TO",
+            other => other,
+        }
+        .to_string()
+    }
+
+    /// How bad the state is, the worst task giving its colour to an array
+    pub fn severity(&self) -> u8 {
+        match self {
+            JobState::Header | JobState::Completed => 0,
+            JobState::Pending => 1,
+            JobState::Running => 2,
+            JobState::Suspended => 3,
+            JobState::Unknown(_) => 4,
+            JobState::Cancelled(_) => 5,
+            JobState::Failed(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            JobState::Completed => "COMPLETED".to_string(),
-            JobState::Running => "RUNNING".to_string(),
-            JobState::Failed => "FAILED".to_string(),
-            JobState::Pending => "PENDING".to_string(),
-            JobState::Header => "State".to_string(),
-            JobState::Cancelled(s) => "CANCELLED".to_string() + "(" + s + ")",
-            JobState::Unknown(s) => format!("Unknown({})", s),
+            JobState::Completed => write!(f, "COMPLETED"),
+            JobState::Running => write!(f, "RUNNING"),
+            JobState::Failed(s) => write!(f, "{}", s),
+            JobState::Pending => write!(f, "PENDING"),
+            JobState::Suspended => write!(f, "SUSPENDED"),
+            JobState::Header => write!(f, "State"),
+            JobState::Cancelled(s) => write!(f, "CANCELLED({})", s),
+            JobState::Unknown(s) => write!(f, "Unknown({})", s),
         }
     }
 }
@@ -110,8 +173,9 @@ impl Colorable for JobState {
         match self {
             JobState::Completed => Color::Green,
             JobState::Running => Color::LightGreen,
-            JobState::Failed => Color::Red,
+            JobState::Failed(_) => Color::Red,
             JobState::Pending => Color::White,
+            JobState::Suspended => Color::Yellow,
             JobState::Header => Color::White,
             JobState::Cancelled(_) => Color::LightRed,
             JobState::Unknown(_) => Color::Cyan,
@@ -180,6 +244,29 @@ impl NumberOrCol {
     }
 }
 
+fn format_kilobytes(kilobytes: usize) -> String {
+    match kilobytes {
+        k if k >= 1 << 20 => format!("{:.1}G", k as f64 / (1 << 20) as f64),
+        k if k >= 1 << 10 => format!("{:.1}M", k as f64 / (1 << 10) as f64),
+        k => format!("{}K", k),
+    }
+}
+
+fn format_secs(secs: usize) -> String {
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+    if days > 0 {
+        format!("{}-{}", days, time)
+    } else {
+        time
+    }
+}
+
 fn parse_elapsed_format_secs(s: &str) -> Result<usize> {
     // TODO(lhenches): handle optional [days-]
     let usable_string = if s.contains('.') {
@@ -237,10 +324,20 @@ impl JobFields {
             elapsed,
             time_limit,
             cpu_time_raw,
+            reason: slice[15].clone(),
+            job_id_raw: slice[16].clone(),
+            group_summary: None,
         };
         Ok(job_fields)
     }
 
+    /// Header line, as the first line of the sacct output
+    pub fn header() -> Result<JobFields> {
+        let mut columns: Vec<_> = SACCT_MAP.entries().collect();
+        columns.sort_by_key(|(_, &i)| i);
+        JobFields::from_slice(columns.into_iter().map(|(c, _)| c.to_string()).collect())
+    }
+
     pub fn get_mem_eff(&self) -> String {
         match (self.reqmem.clone().take(), self.maxrss.clone().take()) {
             (Some(req), Some(max)) => {
@@ -320,18 +417,75 @@ impl JobFields {
         )
     }
 
+    /// Id shown in the list, tasks and steps being placed under their array or job
+    fn indented_id(&self) -> String {
+        if self.is_step() || self.is_array_task() || self.is_het_component() {
+            format!("└ {}", self.job_id)
+        } else {
+            self.job_id.clone()
+        }
+    }
+
+    /// Typed id, none for the header or a row standing for a whole array
+    pub fn id(&self) -> Option<JobId> {
+        JobId::parse(&self.job_id)
+    }
+
+    /// Steps are listed as `<job id>.<step>`, like `1234.batch` or `1234.0`
+    pub fn is_step(&self) -> bool {
+        self.id().is_some_and(|id| id.is_step())
+    }
+
+    /// Task of an array, listed on its own under the array
+    pub fn is_array_task(&self) -> bool {
+        self.id().is_some_and(|id| id.is_array_task()) && self.group_summary.is_none()
+    }
+
+    /// Component of a het job, listed as `<het job id>+<offset>` under the het job
+    pub fn is_het_component(&self) -> bool {
+        self.id().is_some_and(|id| id.is_het_component()) && self.group_summary.is_none()
+    }
+
+    /// Steps show what they used instead of how they were submitted
+    fn step_display_lines(&self) -> String {
+        let usage = format!(
+            "MaxRSS {:>7} Elapsed {} CPU {}",
+            format_kilobytes(self.maxrss.clone().take().unwrap_or(0)),
+            format_secs(self.elapsed.clone().take().unwrap_or(0)),
+            format_secs(self.cpu_time_raw.clone().take().unwrap_or(0)),
+        );
+        [
+            Self::format_str(&self.indented_id(), 15),
+            Self::format_str(&self.job_name, 20),
+            Self::format_str("", 14),
+            Self::format_str(&self.alloc_cpus.as_string(), 14),
+            Self::format_str(&self.state.to_string(), 35),
+            Self::format_str(&self.exit_code, 10),
+            usage,
+        ]
+        .join(" ")
+    }
+
     pub fn display_lines(&self, efficiency_display: bool) -> String {
+        if self.is_step() {
+            return self.step_display_lines();
+        }
         let submit_fmt = if let Some(ref date) = self.submit {
             date.format("%Y-%m-%d %H:%M:%S").to_string()
         } else {
             "Submit".to_string()
         };
         let mut vec_strings_display = vec![
-            Self::format_str(&self.job_id, 15),
+            Self::format_str(&self.indented_id(), 15),
             Self::format_str(&self.job_name, 20),
             Self::format_str(&self.partition, 14),
             Self::format_str(&self.alloc_cpus.as_string(), 14),
-            Self::format_str(&self.state.to_string(), 35),
+            Self::format_str(
+                self.group_summary
+                    .as_ref()
+                    .unwrap_or(&self.state.to_string()),
+                35,
+            ),
         ];
         if efficiency_display {
             vec_strings_display.extend([
@@ -349,20 +503,19 @@ impl JobFields {
         vec_strings_display.join(" ")
     }
 }
-pub fn fetch_logs(run_mode: RunMode, fields: &JobFields) -> Result<Vec<String>> {
-    // try to get log file
-    let find_result =
-        job_handler::get_log_files_finished_job(run_mode, &fields.workdir, &fields.job_id)?;
-    // parse logs into multiple files
-    let vec_logs = if !find_result.is_empty() {
-        find_result
-            .trim_end_matches('\n')
-            .split('\n')
-            .map(|s| s.to_string())
-            .collect()
-    } else {
-        vec![]
-    };
 
-    Ok(vec_logs)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cluster_failures_rank_with_failed() {
+        for state in ["NODE_FAIL", "BOOT_FAIL", "PREEMPTED"] {
+            let state = JobState::from_str(state);
+            assert!(matches!(state, JobState::Failed(_)));
+            assert!(state.severity() > JobState::from_str("CANCELLED by 1000").severity());
+        }
+        assert_eq!(JobState::from_str("NODE_FAIL").short_name(), "NF");
+        assert_eq!(JobState::from_str("FAILED").to_string(), "FAILED");
+    }
 }
@@ -0,0 +1,191 @@
+use super::fixture::{FixtureSource, DEFAULT_FIXTURE_DIR};
+use super::job_action::JobAction;
+use super::job_handler;
+use super::job_handler::SacctFormat;
+use super::job_parser::JobFields;
+use super::log_page::{FileSearch, LogPage, PageRange};
+use super::log_paths::LogSearch;
+use super::rest::RestSource;
+use super::ssh::SshSource;
+use crate::config::Config;
+use crate::parser::RunMode;
+use crate::Cli;
+use chrono::{Local, NaiveDateTime};
+use color_eyre::eyre::{Report, Result};
+use std::cell::Cell;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Start or end of a time window, relative bounds following the current time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeBound {
+    HoursAgo(u32),
+    At(NaiveDateTime),
+}
+
+impl TimeBound {
+    /// Time as accepted by the -S and -E options of sacct
+    pub fn sacct_arg(&self) -> String {
+        match self {
+            TimeBound::HoursAgo(0) => "now".to_string(),
+            TimeBound::HoursAgo(hours) => format!("now-{}hours", hours),
+            TimeBound::At(date) => date.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        }
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        match self {
+            TimeBound::HoursAgo(hours) => Local::now().timestamp() - *hours as i64 * 3600,
+            TimeBound::At(date) => match date.and_local_timezone(Local).earliest() {
+                Some(d) => d.timestamp(),
+                None => date.and_utc().timestamp(),
+            },
+        }
+    }
+
+    /// Bound `hours` before this one
+    pub fn earlier(self, hours: u32) -> TimeBound {
+        match self {
+            TimeBound::HoursAgo(ago) => TimeBound::HoursAgo(ago.saturating_add(hours)),
+            TimeBound::At(date) => TimeBound::At(
+                date.checked_sub_signed(chrono::Duration::hours(hours as i64))
+                    .unwrap_or(NaiveDateTime::MIN),
+            ),
+        }
+    }
+
+    /// Bound `hours` after this one, none once it is now or later
+    pub fn later(self, hours: u32) -> Option<TimeBound> {
+        let later = match self {
+            TimeBound::HoursAgo(ago) => TimeBound::HoursAgo(ago.checked_sub(hours)?),
+            TimeBound::At(date) => {
+                TimeBound::At(date.checked_add_signed(chrono::Duration::hours(hours as i64))?)
+            }
+        };
+        (later.timestamp() < Local::now().timestamp()).then_some(later)
+    }
+
+    pub fn display(&self) -> String {
+        match self {
+            TimeBound::HoursAgo(0) => "now".to_string(),
+            TimeBound::HoursAgo(hours) if hours % 24 == 0 => format!("{} days ago", hours / 24),
+            TimeBound::HoursAgo(hours) => format!("{} hours ago", hours),
+            TimeBound::At(date) => date.format("%Y-%m-%d %H:%M").to_string(),
+        }
+    }
+}
+
+/// Jobs to list, `end` being now when missing
+#[derive(Clone, Copy, Debug)]
+pub struct TimeWindow {
+    pub start: TimeBound,
+    pub end: Option<TimeBound>,
+}
+
+/// Where sboard gets its jobs and logs from
+pub trait JobSource: Send {
+    /// Every sacct row (jobs and steps) in the time window, header line included
+    fn list_jobs(&self, window: &TimeWindow) -> Result<Vec<JobFields>>;
+    /// Paths of the log files of a job
+    fn list_logs(&self, job: &JobFields) -> Result<Vec<String>>;
+    fn read_log(&self, path: &str) -> Result<String>;
+    /// Page of the log, a log file being too large to read whole
+    fn read_log_page(&self, path: &str, range: PageRange) -> Result<LogPage>;
+    /// Offset of the line of the next match in the log, none when it has no match
+    fn search_log(&self, path: &str, search: &FileSearch) -> Result<Option<u64>>;
+    /// Batch scripts found under `dir`
+    fn list_scripts(&self, dir: &str) -> Result<Vec<String>>;
+    /// Every field known about a job
+    fn job_details(&self, job_id: &str) -> Result<JobDetails>;
+    /// Acts on a job, returning a message for the user
+    fn run_action(&self, action: &JobAction) -> Result<String>;
+    /// Releases the resources held by the source, such as a shared ssh connection
+    fn close(&self) {}
+}
+
+pub fn from_cli(cli: &Cli, config: &Config) -> Result<Box<dyn JobSource>> {
+    if let Some(ref fixture_dir) = cli.fixture {
+        return Ok(Box::new(FixtureSource::new(fixture_dir, &config.logs)));
+    }
+    let source: Box<dyn JobSource> = match cli.run_mode {
+        RunMode::Slurm => Box::new(SlurmSource {
+            sacct_format: Cell::new(cli.sacct_format),
+            log_search: LogSearch::new(&config.logs),
+        }),
+        RunMode::Ssh => Box::new(SshSource::new(
+            &cli.ssh,
+            &config.ssh,
+            &config.logs,
+            cli.sacct_format,
+        )?),
+        RunMode::Rest => Box::new(RestSource::new(cli.rest_url.as_deref(), &config.rest)?),
+        RunMode::FromFile => Box::new(FixtureSource::new(
+            Path::new(DEFAULT_FIXTURE_DIR),
+            &config.logs,
+        )),
+    };
+    Ok(source)
+}
+
+/// Fields of a job as name and value, in display order
+pub type JobDetails = Vec<(String, String)>;
+
+/// Sources that get their data by running slurm and coreutils commands
+pub trait CommandRunner {
+    fn output(&self, cmd: &str, command_args: &[&str]) -> Result<Output>;
+
+    /// Standard output of the command, whatever its exit status
+    fn run_command(&self, cmd: &str, command_args: &[&str]) -> Result<String> {
+        Ok(String::from_utf8(self.output(cmd, command_args)?.stdout)?)
+    }
+
+    /// Standard output of the command, or an error with its error output if it failed
+    fn run_checked(&self, cmd: &str, command_args: &[&str]) -> Result<String> {
+        let output = self.output(cmd, command_args)?;
+        if output.status.success() {
+            Ok(String::from_utf8(output.stdout)?)
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(Report::msg(format!("{} failed: {}", cmd, stderr.trim())))
+        }
+    }
+}
+
+/// Runs commands on the local machine
+pub struct SlurmSource {
+    sacct_format: Cell<SacctFormat>,
+    log_search: LogSearch,
+}
+
+impl CommandRunner for SlurmSource {
+    fn output(&self, cmd: &str, command_args: &[&str]) -> Result<Output> {
+        Ok(Command::new(cmd).args(command_args).output()?)
+    }
+}
+
+impl JobSource for SlurmSource {
+    fn list_jobs(&self, window: &TimeWindow) -> Result<Vec<JobFields>> {
+        job_handler::list_jobs_sacct(self, window, &self.sacct_format)
+    }
+    fn list_logs(&self, job: &JobFields) -> Result<Vec<String>> {
+        job_handler::list_logs(self, job, &self.log_search)
+    }
+    fn read_log(&self, path: &str) -> Result<String> {
+        job_handler::read_file(self, path)
+    }
+    fn read_log_page(&self, path: &str, range: PageRange) -> Result<LogPage> {
+        job_handler::read_file_page(self, path, range)
+    }
+    fn search_log(&self, path: &str, search: &FileSearch) -> Result<Option<u64>> {
+        job_handler::search_file(self, path, search)
+    }
+    fn list_scripts(&self, dir: &str) -> Result<Vec<String>> {
+        job_handler::find_scripts(self, dir)
+    }
+    fn job_details(&self, job_id: &str) -> Result<JobDetails> {
+        job_handler::job_details(self, job_id)
+    }
+    fn run_action(&self, action: &JobAction) -> Result<String> {
+        job_handler::run_action(self, action)
+    }
+}
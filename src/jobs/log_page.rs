@@ -0,0 +1,265 @@
+//! Log files read a page of bytes at a time, so that a log of several GB is
+//! never read, or sent through ssh, whole
+use regex::bytes::RegexBuilder;
+use std::cmp::min;
+
+/// Bytes read at once
+pub static PAGE_SIZE: u64 = 256 * 1024;
+
+/// Part of the file to read, relative to what the viewer holds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PageRange {
+    Head,
+    Tail,
+    /// Page ending at the offset, the first byte held
+    Before(u64),
+    /// Page starting at the offset, the end of what is held
+    After(u64),
+    /// Page starting at the line of a match found in the file, in place of
+    /// what is held
+    At(u64),
+}
+
+impl PageRange {
+    /// First byte to read, once the size of the file is known
+    pub fn start(self, file_size: u64) -> u64 {
+        match self {
+            PageRange::Head => 0,
+            PageRange::Tail => file_size.saturating_sub(PAGE_SIZE),
+            PageRange::Before(offset) => offset.saturating_sub(PAGE_SIZE),
+            PageRange::After(offset) | PageRange::At(offset) => offset,
+        }
+    }
+
+    /// Bytes to read from the start, a page before stopping at what is held
+    pub fn read_len(self) -> u64 {
+        match self {
+            PageRange::Before(offset) => min(offset, PAGE_SIZE),
+            _ => PAGE_SIZE,
+        }
+    }
+
+    /// Whether the page starts at the beginning of a line
+    fn starts_line(self) -> bool {
+        matches!(
+            self,
+            PageRange::Head | PageRange::After(_) | PageRange::At(_)
+        )
+    }
+}
+
+/// Search of a pattern over the whole log, from the loaded lines on
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileSearch {
+    pub pattern: String,
+    pub ignore_case: bool,
+    /// Searched from here to the end of the file, or to its start when
+    /// searching backward, then from the other end
+    pub offset: u64,
+    pub backward: bool,
+}
+
+impl FileSearch {
+    /// Offset of the line of the first match in `bytes`, the whole file,
+    /// after the search's offset or before it when searching backward
+    pub fn find_in(&self, bytes: &[u8]) -> Result<Option<u64>, regex::Error> {
+        let regex = RegexBuilder::new(&self.pattern)
+            .case_insensitive(self.ignore_case)
+            .build()?;
+        let mut line_start = 0;
+        let mut found = vec![];
+        for line in bytes.split(|&b| b == b'\n') {
+            if regex.is_match(line) {
+                found.push(line_start);
+            }
+            line_start += line.len() as u64 + 1;
+        }
+        // like vim, the search wraps around the end of the file
+        let next = match self.backward {
+            true => found.iter().rev().find(|&&o| o < self.offset),
+            false => found.iter().find(|&&o| o >= self.offset),
+        };
+        let first = match self.backward {
+            true => found.last(),
+            false => found.first(),
+        };
+        Ok(next.or(first).copied())
+    }
+}
+
+/// Whole lines of the file between `start` and `end`. The last line lacks its
+/// newline, and is empty when the page ends with one.
+#[derive(Clone, Debug)]
+pub struct LogPage {
+    pub lines: Vec<String>,
+    /// Bytes of each line in the file, newline included
+    pub line_bytes: Vec<u64>,
+    pub start: u64,
+    pub end: u64,
+    pub file_size: u64,
+}
+
+impl LogPage {
+    /// Page of the bytes read from `start`, cut to whole lines. The line cut
+    /// at either end is left for the next page, unless it is longer than a
+    /// page, and a character cut at the end of the file for the next read.
+    pub fn from_bytes(range: PageRange, bytes: &[u8], start: u64, file_size: u64) -> Self {
+        let mut first = 0;
+        if start > 0 && !range.starts_line() {
+            if let Some(i) = bytes.iter().position(|&b| b == b'\n') {
+                if i + 1 < bytes.len() {
+                    first = i + 1;
+                }
+            }
+        }
+        let mut last = bytes.len();
+        let read_end = start + bytes.len() as u64;
+        if read_end < file_size {
+            if let Some(i) = bytes[first..].iter().rposition(|&b| b == b'\n') {
+                last = first + i + 1;
+            }
+        } else if let Err(e) = std::str::from_utf8(&bytes[first..]) {
+            if e.error_len().is_none() {
+                last = first + e.valid_up_to();
+            }
+        }
+        let bytes = &bytes[first..last];
+        let mut lines = vec![];
+        let mut line_bytes = vec![];
+        for line in bytes.split(|&b| b == b'\n') {
+            lines.push(String::from_utf8_lossy(line).to_string());
+            line_bytes.push(line.len() as u64 + 1);
+        }
+        // the last line has no newline
+        if let Some(last_bytes) = line_bytes.last_mut() {
+            *last_bytes -= 1;
+        }
+        LogPage {
+            lines,
+            line_bytes,
+            start: start + first as u64,
+            end: start + last as u64,
+            // the file may have grown since its size was read
+            file_size: file_size.max(read_end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FILE: &str = "one\ntwo\nthree\nfour";
+
+    /// Page of FILE read like `read_file_page` does
+    fn page(range: PageRange, start: u64, end: u64) -> LogPage {
+        let bytes = &FILE.as_bytes()[start as usize..end as usize];
+        let page = LogPage::from_bytes(range, bytes, start, FILE.len() as u64);
+        assert_eq!(page.line_bytes.iter().sum::<u64>(), page.end - page.start);
+        assert_eq!(
+            page.lines.join("\n"),
+            FILE[page.start as usize..page.end as usize]
+        );
+        page
+    }
+
+    #[test]
+    fn page_ranges() {
+        assert_eq!(PageRange::Tail.start(100), 0);
+        assert_eq!(PageRange::Tail.start(PAGE_SIZE + 10), 10);
+        assert_eq!(PageRange::Before(10).start(1000), 0);
+        assert_eq!(PageRange::Before(10).read_len(), 10);
+        assert_eq!(PageRange::Before(PAGE_SIZE + 10).read_len(), PAGE_SIZE);
+        assert_eq!(PageRange::After(10).start(1000), 10);
+        assert_eq!(PageRange::At(10).start(1000), 10);
+    }
+
+    #[test]
+    fn whole_file() {
+        let page = page(PageRange::Head, 0, FILE.len() as u64);
+        assert_eq!(page.lines, ["one", "two", "three", "four"]);
+        assert_eq!(page.line_bytes, [4, 4, 6, 4]);
+    }
+
+    #[test]
+    fn partial_first_line_dropped() {
+        // "o\ntwo\nthree\nfour": the end of "two" is left for the page before
+        let tail = page(PageRange::Tail, 6, FILE.len() as u64);
+        assert_eq!(tail.lines, ["three", "four"]);
+        assert_eq!((tail.start, tail.end), (8, 18));
+        let before = page(PageRange::Before(14), 2, 14);
+        assert_eq!(before.lines, ["two", "three", ""]);
+        assert_eq!((before.start, before.end), (4, 14));
+        // a page after the loaded lines starts a line
+        let after = page(PageRange::After(4), 4, 10);
+        assert_eq!(after.lines, ["two", ""]);
+    }
+
+    #[test]
+    fn partial_last_line_left_for_the_next_page() {
+        let head = page(PageRange::Head, 0, 10);
+        assert_eq!(head.lines, ["one", "two", ""]);
+        assert_eq!(head.end, 8);
+    }
+
+    #[test]
+    fn line_longer_than_a_page() {
+        let line = "x".repeat(100);
+        let file_size = 1000;
+        let page = LogPage::from_bytes(PageRange::Before(500), line.as_bytes(), 400, file_size);
+        assert_eq!(page.lines, [line.as_str()]);
+        assert_eq!((page.start, page.end), (400, 500));
+        // ending with its newline, the line is not dropped either
+        let ended = format!("{}\n", line);
+        let page = LogPage::from_bytes(PageRange::Tail, ended.as_bytes(), 899, file_size);
+        assert_eq!(page.lines, [line.as_str(), ""]);
+        assert_eq!(page.line_bytes, [101, 0]);
+    }
+
+    #[test]
+    fn character_cut_at_the_end_of_the_file() {
+        let text = "ab\né";
+        let cut = &text.as_bytes()[..4];
+        let page = LogPage::from_bytes(PageRange::Head, cut, 0, 4);
+        assert_eq!(page.lines, ["ab", ""]);
+        assert_eq!(page.end, 3);
+        // once written whole, it is read
+        let page = LogPage::from_bytes(PageRange::After(3), &text.as_bytes()[3..], 3, 5);
+        assert_eq!(page.lines, ["é"]);
+        assert_eq!(page.end, 5);
+    }
+
+    #[test]
+    fn file_grown_since_its_size_was_read() {
+        let page = LogPage::from_bytes(PageRange::After(0), b"one\ntwo\n", 0, 4);
+        assert_eq!(page.file_size, 8);
+        assert_eq!(page.lines, ["one", "two", ""]);
+    }
+
+    #[test]
+    fn search_wraps_around_the_file() {
+        let search = |pattern: &str, offset: u64, backward: bool| {
+            let search = FileSearch {
+                pattern: pattern.to_string(),
+                ignore_case: false,
+                offset,
+                backward,
+            };
+            search.find_in(FILE.as_bytes()).unwrap()
+        };
+        assert_eq!(search("t", 0, false), Some(4));
+        assert_eq!(search("t", 5, false), Some(8));
+        assert_eq!(search("t", 9, false), Some(4));
+        assert_eq!(search("t", 8, true), Some(4));
+        assert_eq!(search("t", 4, true), Some(8));
+        assert_eq!(search("^f", 18, true), Some(14));
+        assert_eq!(search("FOUR", 0, false), None);
+        let ignore_case = FileSearch {
+            pattern: "FOUR".to_string(),
+            ignore_case: true,
+            offset: 0,
+            backward: false,
+        };
+        assert_eq!(ignore_case.find_in(FILE.as_bytes()).unwrap(), Some(14));
+    }
+}
@@ -0,0 +1,499 @@
+//! Log paths given to sbatch with `--output` and `--error`, and the expansion
+//! of their `%` patterns
+use super::job_id::{ArrayPart, JobId};
+use super::job_parser::JobFields;
+use crate::config::LogConfig;
+use crate::submit::parse_directives;
+use std::path::Path;
+
+/// Written by slurm when no `--output` is given
+static DEFAULT_OUTPUT: &str = "slurm-%j.out";
+static DEFAULT_ARRAY_OUTPUT: &str = "slurm-%A_%a.out";
+static DEFAULT_FIND_DEPTH: usize = 2;
+
+/// Where logs are looked for besides the paths the job was given
+#[derive(Clone, Debug)]
+pub struct LogSearch {
+    /// Tried in order after the `--output` and `--error` of the job
+    pub templates: Vec<String>,
+    /// Depth of the last resort search in the workdir
+    pub find_depth: usize,
+}
+
+impl LogSearch {
+    pub fn new(config: &LogConfig) -> Self {
+        LogSearch {
+            templates: config.templates.clone(),
+            find_depth: config.find_depth.unwrap_or(DEFAULT_FIND_DEPTH),
+        }
+    }
+
+    /// Patterns of the job followed by the configured templates
+    pub fn patterns(&self, job: &JobFields, script: Option<&str>) -> Vec<String> {
+        let mut patterns = log_patterns(job, script);
+        for template in &self.templates {
+            if !patterns.contains(template) {
+                patterns.push(template.clone());
+            }
+        }
+        patterns
+    }
+}
+
+/// sbatch options that take no value, so the next word is not theirs
+static FLAGS: [&str; 24] = [
+    "-h",
+    "-H",
+    "-I",
+    "-k",
+    "-O",
+    "-Q",
+    "-s",
+    "-v",
+    "-V",
+    "-W",
+    "--contiguous",
+    "--exclusive",
+    "--help",
+    "--hold",
+    "--ignore-pbs",
+    "--no-requeue",
+    "--overcommit",
+    "--oversubscribe",
+    "--parsable",
+    "--quiet",
+    "--requeue",
+    "--test-only",
+    "--verbose",
+    "--wait",
+];
+
+/// Words of the submit line, quotes removed
+fn words(submit_line: &str) -> Vec<String> {
+    submit_line
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c| c == '\'' || c == '"').to_string())
+        .collect()
+}
+
+/// Options of the submit line with their value, and the script after them
+fn parse_submit_line(submit_line: &str) -> (Vec<(String, Option<String>)>, Option<String>) {
+    let mut words = words(submit_line).into_iter().skip(1);
+    let mut options = vec![];
+    while let Some(word) = words.next() {
+        if !word.starts_with('-') {
+            return (options, Some(word));
+        }
+        let option = match word.split_once('=') {
+            Some((option, value)) => (option.to_string(), Some(value.to_string())),
+            None if FLAGS.contains(&word.as_str()) => (word, None),
+            // short options may be glued to their value, like -c16
+            None if !word.starts_with("--") && word.len() > 2 => {
+                let (option, value) = word.split_at(2);
+                (option.to_string(), Some(value.to_string()))
+            }
+            None => {
+                let value = words.next();
+                (word, value)
+            }
+        };
+        options.push(option);
+    }
+    (options, None)
+}
+
+/// Path of the batch script on the cluster, none for `--wrap` jobs
+pub fn script_path(job: &JobFields) -> Option<String> {
+    let (options, script) = parse_submit_line(&job.submit_line);
+    if options.iter().any(|(option, _)| option == "--wrap") {
+        return None;
+    }
+    Some(in_workdir(&script?, &job.workdir))
+}
+
+fn in_workdir(path: &str, workdir: &str) -> String {
+    match path.starts_with('/') {
+        true => path.to_string(),
+        false => Path::new(workdir).join(path).display().to_string(),
+    }
+}
+
+/// Value of `--output` or `--error` in the options, by their long or short name
+fn option_value(options: &[(String, Option<String>)], long: &str, short: &str) -> Option<String> {
+    options
+        .iter()
+        .rev()
+        .find(|(option, _)| option == long || option == short)
+        .and_then(|(_, value)| value.clone())
+}
+
+/// Output and error patterns of the job: the command line takes precedence
+/// over the script, then slurm writes both to its default file
+fn log_patterns(job: &JobFields, script: Option<&str>) -> Vec<String> {
+    let (mut options, _) = parse_submit_line(&job.submit_line);
+    if let Some(script) = script {
+        let directives = parse_directives(script)
+            .into_iter()
+            .map(|d| (d.option, d.value));
+        // the command line comes last so that it wins
+        options = directives.chain(options).collect();
+    }
+    let is_array = log_job_id(job).is_some_and(|id| id.array.is_some())
+        || option_value(&options, "--array", "-a").is_some();
+    let default = match is_array {
+        true => DEFAULT_ARRAY_OUTPUT,
+        false => DEFAULT_OUTPUT,
+    };
+    let output = option_value(&options, "--output", "-o").unwrap_or(default.to_string());
+    let mut patterns = vec![output];
+    if let Some(error) = option_value(&options, "--error", "-e") {
+        if !patterns.contains(&error) {
+            patterns.push(error);
+        }
+    }
+    patterns
+}
+
+/// Id the patterns are expanded with, the job of a step
+fn log_job_id(job: &JobFields) -> Option<JobId> {
+    job.id().map(|id| id.job())
+}
+
+pub fn needs_user(patterns: &[String]) -> bool {
+    patterns.iter().any(|p| p.contains("%u"))
+}
+
+pub fn needs_node(patterns: &[String]) -> bool {
+    patterns.iter().any(|p| p.contains("%N"))
+}
+
+/// Path written by the job for the pattern, with `*` for the task ids of
+/// pending tasks or a whole array, however many there are. The path is a
+/// glob, other wildcard characters being escaped like `[*]`. None when the
+/// pattern needs something unknown, like the node or the job ids the tasks of
+/// an array get once they run.
+pub fn expand(
+    pattern: &str,
+    job: &JobFields,
+    user: Option<&str>,
+    node: Option<&str>,
+) -> Option<String> {
+    if pattern == "/dev/null" {
+        return None;
+    }
+    let id = log_job_id(job)?;
+    let mut path = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push_str(&escape_glob(&c.to_string()));
+            continue;
+        }
+        // numbers may be zero padded, like %4a
+        let mut width = String::new();
+        while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+            width.push(digit);
+        }
+        let width: usize = width.parse().unwrap_or(0);
+        let pad = |n: u64| format!("{:0width$}", n, width = width);
+        let value = match chars.next()? {
+            '%' => "%".to_string(),
+            'A' => pad(id.base),
+            'a' => match &id.array {
+                Some(ArrayPart::Task(task)) => pad(*task as u64),
+                Some(ArrayPart::Range { .. }) => "*".to_string(),
+                // what slurm writes for a job outside an array
+                None => "4294967294".to_string(),
+            },
+            // an array task has its own job id, unknown until it runs, and
+            // `*` would match the logs of any job
+            'j' => match &id.array {
+                Some(ArrayPart::Task(_)) => pad(JobId::parse(&job.job_id_raw)?.base),
+                Some(ArrayPart::Range { .. }) => return None,
+                // the script of a het job runs in its first component, under
+                // the het job id
+                None => pad(id.base),
+            },
+            'x' => escape_glob(&job.job_name),
+            'u' => escape_glob(user?),
+            'N' => escape_glob(node?),
+            'n' | 't' => pad(0),
+            's' => "batch".to_string(),
+            _ => return None,
+        };
+        path.push_str(&value);
+    }
+    Some(in_workdir(&path, &escape_glob(&job.workdir)))
+}
+
+/// Text matched literally by a glob, its wildcard characters put in brackets
+/// since a shell leaves backslashes in a glob matching no file
+fn escape_glob(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '*' | '?' | '[' => {
+                escaped.push('[');
+                escaped.push(c);
+                escaped.push(']');
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Literal parts of a glob between its `*` wildcards
+fn glob_parts(pattern: &str) -> Vec<String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '[' if chars.get(i + 2) == Some(&']') => {
+                part.push(chars[i + 1]);
+                i += 2;
+            }
+            '*' => parts.push(std::mem::take(&mut part)),
+            c => part.push(c),
+        }
+        i += 1;
+    }
+    parts.push(part);
+    parts
+}
+
+/// Path matched by a glob without wildcard, like a directory of the logs
+pub fn unescape_glob(pattern: &str) -> String {
+    glob_parts(pattern).join("*")
+}
+
+/// First node of a node list like `node[03-05,9]`
+pub fn first_node(node_list: &str) -> Option<String> {
+    let node_list = node_list.trim();
+    match node_list.split_once('[') {
+        Some((prefix, ranges)) => {
+            let first = ranges.split([',', '-', ']']).next()?;
+            Some(format!("{}{}", prefix, first))
+        }
+        None => node_list
+            .split(',')
+            .next()
+            .filter(|n| !n.is_empty() && *n != "None")
+            .map(|n| n.to_string()),
+    }
+}
+
+/// Whether the file name matches a glob from `expand`, whose only wildcard is `*`
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let parts = glob_parts(pattern);
+    let Some(mut rest) = name.strip_prefix(parts[0].as_str()) else {
+        return false;
+    };
+    let parts = &parts[1..];
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(job_id: &str, submit_line: &str) -> JobFields {
+        let fields = [
+            job_id,
+            "train",
+            "gpu",
+            "lab",
+            "1",
+            "COMPLETED",
+            "0:0",
+            submit_line,
+            "/home/jdoe/project",
+            "2024-03-11T09:12:40",
+            "1G",
+            "",
+            "60",
+            "120",
+            "00:00:59",
+            "None",
+            job_id,
+        ];
+        JobFields::from_slice(fields.iter().map(|f| f.to_string()).collect()).unwrap()
+    }
+
+    fn paths(pattern: &str, job_id: &str) -> Vec<String> {
+        expand(
+            pattern,
+            &job(job_id, "sbatch run.sh"),
+            Some("jdoe"),
+            Some("node03"),
+        )
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn expand_placeholders() {
+        let dir = "/home/jdoe/project";
+        assert_eq!(
+            paths("%x_%j.out", "1234"),
+            [format!("{}/train_1234.out", dir)]
+        );
+        assert_eq!(paths("%A_%a.out", "12_3"), [format!("{}/12_3.out", dir)]);
+        assert_eq!(
+            paths("%A_%4a.out", "12_3"),
+            [format!("{}/12_0003.out", dir)]
+        );
+        assert_eq!(paths("%8j.out", "1234"), [format!("{}/00001234.out", dir)]);
+        assert_eq!(
+            paths("/scratch/%u/%N-%j.log", "1234"),
+            ["/scratch/jdoe/node03-1234.log"]
+        );
+        assert_eq!(paths("100%%_%j", "1234"), [format!("{}/100%_1234", dir)]);
+        // outside an array, slurm writes the largest task id
+        assert_eq!(paths("%a.out", "1234"), [format!("{}/4294967294.out", dir)]);
+        // the step of a het component is the het job's
+        assert_eq!(paths("%j.out", "1234+1.0"), [format!("{}/1234.out", dir)]);
+        assert!(paths("%q.out", "1234").is_empty());
+    }
+
+    #[test]
+    fn expand_arrays() {
+        let dir = "/home/jdoe/project";
+        // one glob for the pending tasks, however many
+        assert_eq!(
+            paths("%A_%a.out", "12_[1-100000%2]"),
+            [format!("{}/12_*.out", dir)]
+        );
+        assert_eq!(
+            paths("%A_%a.out", "12_[1-3]"),
+            [format!("{}/12_*.out", dir)]
+        );
+        // a task that ran has its own job id
+        let mut task = job("12_3", "sbatch --array=1-3 run.sh");
+        task.job_id_raw = "15".to_string();
+        assert_eq!(
+            expand("slurm-%j.out", &task, None, None).as_deref(),
+            Some("/home/jdoe/project/slurm-15.out")
+        );
+        task.job_id_raw = "15.batch".to_string();
+        assert_eq!(
+            expand("%A_%a-%j.out", &task, None, None).as_deref(),
+            Some("/home/jdoe/project/12_3-15.out")
+        );
+        // pending tasks have none yet
+        assert!(paths("%j.out", "12_[4-6]").is_empty());
+        assert!(paths("%x_%j.out", "12_[1-3]").is_empty());
+    }
+
+    #[test]
+    fn expand_needs_user_and_node() {
+        let job = job("1234", "sbatch run.sh");
+        assert!(expand("%u.out", &job, None, None).is_none());
+        assert!(expand("%N.out", &job, None, None).is_none());
+        assert!(needs_user(&["%u/%j.out".to_string()]));
+        assert!(!needs_node(&["%u/%j.out".to_string()]));
+    }
+
+    #[test]
+    fn expand_escapes_glob_characters() {
+        let mut job = job("12_[1-3]", "sbatch run.sh");
+        job.job_name = "fit[a]*".to_string();
+        job.workdir = "/home/jdoe/my runs?".to_string();
+        let glob = expand("%x_%a.out", &job, None, None);
+        assert_eq!(
+            glob.as_deref(),
+            Some("/home/jdoe/my runs[?]/fit[[]a][*]_*.out")
+        );
+        assert!(glob_matches("fit[[]a][*]_*.out", "fit[a]*_2.out"));
+        assert!(!glob_matches("fit[[]a][*]_*.out", "fita_2.out"));
+        assert_eq!(
+            unescape_glob("/home/jdoe/my runs[?]"),
+            "/home/jdoe/my runs?"
+        );
+    }
+
+    #[test]
+    fn no_log_for_dev_null_or_wrap() {
+        assert!(paths("/dev/null", "1234").is_empty());
+        let wrapped = job("1234", "sbatch --wrap=hostname");
+        assert_eq!(script_path(&wrapped), None);
+        let script = job("1234", "sbatch -p gpu jobs/run.sh --epochs 3");
+        assert_eq!(
+            script_path(&script).as_deref(),
+            Some("/home/jdoe/project/jobs/run.sh")
+        );
+    }
+
+    #[test]
+    fn submit_line_options() {
+        let (options, script) =
+            parse_submit_line("sbatch -oout_%j.log --hold -c 4 --mem=8G 'run.sh' -x");
+        let options: Vec<(&str, Option<&str>)> = options
+            .iter()
+            .map(|(option, value)| (option.as_str(), value.as_deref()))
+            .collect();
+        assert_eq!(
+            options,
+            [
+                ("-o", Some("out_%j.log")),
+                ("--hold", None),
+                ("-c", Some("4")),
+                ("--mem", Some("8G"))
+            ]
+        );
+        assert_eq!(script.as_deref(), Some("run.sh"));
+    }
+
+    #[test]
+    fn submit_line_overrides_directives() {
+        let script = "#!/bin/bash\n#SBATCH --output=script.out\n#SBATCH -e err_%j.txt\necho";
+        let cli = job("1234", "sbatch -o cli_%j.out run.sh");
+        assert_eq!(
+            log_patterns(&cli, Some(script)),
+            ["cli_%j.out", "err_%j.txt"]
+        );
+        assert_eq!(log_patterns(&cli, None), ["cli_%j.out"]);
+        let default = job("1234", "sbatch run.sh");
+        assert_eq!(log_patterns(&default, None), ["slurm-%j.out"]);
+        let array = "#SBATCH --array=1-4\n";
+        assert_eq!(log_patterns(&default, Some(array)), ["slurm-%A_%a.out"]);
+        let search = LogSearch {
+            templates: vec!["logs/%j.log".to_string(), "slurm-%j.out".to_string()],
+            find_depth: 2,
+        };
+        assert_eq!(
+            search.patterns(&default, None),
+            ["slurm-%j.out", "logs/%j.log"]
+        );
+    }
+
+    #[test]
+    fn first_node_of_node_lists() {
+        assert_eq!(first_node("node[03-05,9]").as_deref(), Some("node03"));
+        assert_eq!(first_node("gpu[7]").as_deref(), Some("gpu7"));
+        assert_eq!(first_node("gpu1,gpu2\n").as_deref(), Some("gpu1"));
+        assert_eq!(first_node("None"), None);
+        assert_eq!(first_node(""), None);
+    }
+
+    #[test]
+    fn glob_matches_stars() {
+        assert!(glob_matches("12_*.out", "12_3.out"));
+        assert!(!glob_matches("12_*.out", "123.out"));
+        assert!(!glob_matches("12_*.out", "12_3.err"));
+        assert!(glob_matches("*-*.log", "a-b.log"));
+        assert!(glob_matches("exact.log", "exact.log"));
+        assert!(!glob_matches("exact.log", "exact.log.1"));
+    }
+}
@@ -1,2 +1,12 @@
+pub mod fixture;
+pub mod job_action;
 pub mod job_handler;
+pub mod job_id;
 pub mod job_parser;
+pub mod job_source;
+pub mod log_page;
+pub mod log_paths;
+pub mod rest;
+pub mod sacct_json;
+pub mod shell;
+pub mod ssh;
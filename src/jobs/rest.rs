@@ -0,0 +1,269 @@
+//! Source talking to the slurmrestd REST API, authenticated with a JWT
+//! (see `scontrol token`)
+use super::job_action::JobAction;
+use super::job_parser::JobFields;
+use super::job_source::{JobDetails, JobSource, TimeWindow};
+use super::log_page::{FileSearch, LogPage, PageRange};
+use super::sacct_json;
+use crate::config::RestConfig;
+use color_eyre::eyre::{Context, Report, Result};
+use std::time::Duration;
+
+static DEFAULT_API_VERSION: &str = "v0.0.39";
+static TOKEN_ENV: &str = "SLURM_JWT";
+static MISSING_URL: &str =
+    "rest run mode needs an url: use --rest-url or set `url` in the [rest] config section";
+static TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct RestSource {
+    url: String,
+    api_version: String,
+    user: String,
+    token: String,
+    agent: ureq::Agent,
+}
+
+impl RestSource {
+    /// Builds the source from the config file, `url` coming from the command line
+    pub fn new(url: Option<&str>, config: &RestConfig) -> Result<Self> {
+        let url = url
+            .map(|u| u.to_string())
+            .or(config.url.clone())
+            .ok_or_else(|| Report::msg(MISSING_URL))?;
+        let token = match (&config.token, &config.token_file) {
+            (Some(token), _) => token.clone(),
+            (None, Some(token_file)) => std::fs::read_to_string(token_file)
+                .wrap_err_with(|| format!("Could not read token file {}", token_file.display()))?
+                .trim()
+                .to_string(),
+            (None, None) => std::env::var(TOKEN_ENV).wrap_err(
+                "rest run mode needs a token: set SLURM_JWT, or `token` in the [rest] config section",
+            )?,
+        };
+        let user = match config.user {
+            Some(ref user) => user.clone(),
+            None => {
+                std::env::var("USER").wrap_err("Could not find the user name for slurmrestd")?
+            }
+        };
+        Ok(RestSource {
+            url: url.trim_end_matches('/').to_string(),
+            api_version: config
+                .api_version
+                .clone()
+                .unwrap_or(DEFAULT_API_VERSION.to_string()),
+            user,
+            token,
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+        })
+    }
+
+    fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<String> {
+        self.call("GET", path, query)
+    }
+
+    fn call(&self, method: &str, path: &str, query: &[(&str, &str)]) -> Result<String> {
+        let url = format!("{}/{}", self.url, path);
+        let response = self
+            .agent
+            .request(method, &url)
+            .set("X-SLURM-USER-NAME", &self.user)
+            .set("X-SLURM-USER-TOKEN", &self.token)
+            .query_pairs(query.iter().copied())
+            .call();
+        match response {
+            Ok(response) => Ok(response.into_string()?),
+            // slurmrestd explains errors in the body
+            Err(ureq::Error::Status(code, response)) => Err(Report::msg(format!(
+                "slurmrestd answered {} for {}: {}",
+                code,
+                url,
+                response.into_string().unwrap_or_default()
+            ))),
+            Err(e) => Err(e).wrap_err_with(|| format!("Could not reach slurmrestd at {}", url)),
+        }
+    }
+}
+
+impl JobSource for RestSource {
+    fn list_jobs(&self, window: &TimeWindow) -> Result<Vec<JobFields>> {
+        let path = format!("slurmdb/{}/jobs", self.api_version);
+        let start_time = window.start.timestamp().to_string();
+        let mut query = vec![("users", self.user.as_str()), ("start_time", &start_time)];
+        let end_time = window.end.map(|end| end.timestamp().to_string());
+        if let Some(ref end_time) = end_time {
+            query.push(("end_time", end_time));
+        }
+        let body = self.get(&path, &query)?;
+        // slurmdbd answers with the same document as sacct --json
+        JobFields::from_sacct_json(&body)
+    }
+
+    /// slurmrestd gives no access to files, so no log is ever found
+    fn list_logs(&self, _job: &JobFields) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    fn read_log(&self, _path: &str) -> Result<String> {
+        Err(Report::msg("Log files cannot be read through slurmrestd"))
+    }
+
+    fn read_log_page(&self, _path: &str, _range: PageRange) -> Result<LogPage> {
+        Err(Report::msg("Log files cannot be read through slurmrestd"))
+    }
+
+    fn search_log(&self, _path: &str, _search: &FileSearch) -> Result<Option<u64>> {
+        Err(Report::msg("Log files cannot be read through slurmrestd"))
+    }
+
+    fn job_details(&self, job_id: &str) -> Result<JobDetails> {
+        let path = format!("slurmdb/{}/job/{}", self.api_version, job_id);
+        sacct_json::job_details(&self.get(&path, &[])?, job_id)
+    }
+
+    fn list_scripts(&self, _dir: &str) -> Result<Vec<String>> {
+        Err(Report::msg(
+            "Batch scripts cannot be listed through slurmrestd",
+        ))
+    }
+
+    fn run_action(&self, action: &JobAction) -> Result<String> {
+        match action {
+            JobAction::Cancel {
+                job_id,
+                signal,
+                pending_only: false,
+            } => {
+                let path = format!("slurm/{}/job/{}", self.api_version, job_id);
+                let query: Vec<_> = signal.iter().map(|s| ("signal", s.as_str())).collect();
+                let body = self.call("DELETE", &path, &query)?;
+                sacct_json::check_errors(&body)?;
+                Ok(format!("{}: done", action.description()))
+            }
+            _ => Err(Report::msg(format!(
+                "{}: not supported by slurmrestd",
+                action.description()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::job_source::TimeBound;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+
+    static RECORDED: &str = "test_data/slurmrestd";
+
+    /// Serves the recorded responses like slurmrestd, sending back the path
+    /// and the headers of each request
+    fn serve() -> (String, Receiver<(String, Vec<String>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, requests) = channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut headers = vec![];
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    headers.push(header.trim().to_string());
+                }
+                let target = request_line.split_whitespace().nth(1).unwrap().to_string();
+                let path = target.split('?').next().unwrap().to_string();
+                let (status, body) = match std::fs::read_to_string(format!("{}{}", RECORDED, path))
+                {
+                    Ok(body) => ("200 OK", body),
+                    Err(_) => (
+                        "404 Not Found",
+                        r#"{"errors": [{"description": "Job not found"}]}"#.to_string(),
+                    ),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+                if sender.send((target, headers)).is_err() {
+                    break;
+                }
+            }
+        });
+        (url, requests)
+    }
+
+    fn source(url: &str) -> RestSource {
+        let config = RestConfig {
+            token: Some("secret-jwt".to_string()),
+            user: Some("jdoe".to_string()),
+            ..RestConfig::default()
+        };
+        RestSource::new(Some(url), &config).unwrap()
+    }
+
+    #[test]
+    fn list_jobs_sends_the_jwt() {
+        let (url, requests) = serve();
+        let window = TimeWindow {
+            start: TimeBound::HoursAgo(24),
+            end: None,
+        };
+        let jobs = source(&url).list_jobs(&window).unwrap();
+        let ids: Vec<&str> = jobs.iter().map(|job| job.job_id.as_str()).collect();
+        // an array task is listed under the id of its array
+        assert_eq!(
+            ids,
+            ["JobID", "2001", "2001.batch", "2002_3", "2002_3.batch"]
+        );
+        let (target, headers) = requests.recv().unwrap();
+        assert!(target.starts_with("/slurmdb/v0.0.39/jobs?users=jdoe&start_time="));
+        assert!(headers.contains(&"X-SLURM-USER-NAME: jdoe".to_string()));
+        assert!(headers.contains(&"X-SLURM-USER-TOKEN: secret-jwt".to_string()));
+    }
+
+    #[test]
+    fn job_details_of_a_recorded_job() {
+        let (url, requests) = serve();
+        let details = source(&url).job_details("2001").unwrap();
+        let field = |name: &str| {
+            details
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.clone())
+        };
+        assert_eq!(field("name").as_deref(), Some("train"));
+        assert_eq!(field("partition").as_deref(), Some("gpu"));
+        assert_eq!(field("required.memory_per_node").as_deref(), Some("16384"));
+        let (target, _) = requests.recv().unwrap();
+        assert_eq!(target, "/slurmdb/v0.0.39/job/2001");
+    }
+
+    #[test]
+    fn errors_show_the_answer_of_slurmrestd() {
+        let (url, _requests) = serve();
+        let error = source(&url).job_details("9999").unwrap_err().to_string();
+        assert!(error.contains("answered 404"), "{}", error);
+        assert!(error.contains("Job not found"), "{}", error);
+    }
+
+    #[test]
+    fn unreachable_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let error = source(&url).job_details("2001").unwrap_err().to_string();
+        assert!(error.contains("Could not reach slurmrestd"), "{}", error);
+    }
+}
@@ -0,0 +1,670 @@
+//! Parsing of `sacct --json`, mapped onto the same rows as the pipe-delimited output:
+//! one row per job followed by one row per step.
+//! Field layouts differ between slurm data_parser versions, so most fields accept
+//! several shapes.
+use super::job_id::JobId;
+use super::job_parser::{JobFields, JobState, NumberOrCol};
+use super::job_source::JobDetails;
+use chrono::{DateTime, Local, NaiveDateTime};
+use color_eyre::eyre::{Report, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct SacctJson {
+    #[serde(default)]
+    jobs: Vec<JsonJob>,
+    #[serde(default)]
+    errors: Vec<JsonError>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonError {
+    description: String,
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorsOnly {
+    #[serde(default)]
+    errors: Vec<JsonError>,
+}
+
+fn first_error(errors: &[JsonError]) -> Result<()> {
+    match errors.first() {
+        Some(error) => Err(Report::msg(format!(
+            "slurm error: {} {}",
+            error.error, error.description
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Fails with the first error listed in a slurm json answer, if any
+pub fn check_errors(answer: &str) -> Result<()> {
+    let answer: ErrorsOnly = serde_json::from_str(answer)?;
+    first_error(&answer.errors)
+}
+
+/// Every field of the job, flattened to dotted names like `time.start`.
+/// Array tasks are found by the id of their array.
+pub fn job_details(sacct_res: &str, job_id: &str) -> Result<JobDetails> {
+    check_errors(sacct_res)?;
+    let answer: serde_json::Value = serde_json::from_str(sacct_res)?;
+    let jobs = answer["jobs"].as_array().cloned().unwrap_or_default();
+    let wanted = JobId::parse(job_id);
+    let job = jobs
+        .iter()
+        .find(|job| match &wanted {
+            // a het component `12+1` is found from its het job id and offset
+            Some(JobId {
+                base,
+                het_offset: Some(offset),
+                ..
+            }) => {
+                job["het"]["job_id"].as_u64() == Some(*base)
+                    && json_u64(&job["het"]["job_offset"]) == Some(*offset as u64)
+            }
+            Some(JobId { base, .. }) => {
+                job["job_id"].as_u64() == Some(*base)
+                    || job["array"]["job_id"].as_u64() == Some(*base)
+            }
+            None => false,
+        })
+        .ok_or(Report::msg(format!(
+            "No accounting data for job {}",
+            job_id
+        )))?;
+    let mut details = vec![];
+    flatten("", job, &mut details);
+    Ok(details)
+}
+
+fn flatten(name: &str, value: &serde_json::Value, details: &mut JobDetails) {
+    use serde_json::Value;
+    let child = |key: &str| match name {
+        "" => key.to_string(),
+        _ => format!("{}.{}", name, key),
+    };
+    let text = match value {
+        // numbers of recent slurm versions
+        Value::Object(map) if map.contains_key("number") => {
+            match (map.get("set"), map.get("infinite")) {
+                (_, Some(Value::Bool(true))) => "UNLIMITED".to_string(),
+                (Some(Value::Bool(false)), _) => String::new(),
+                _ => map["number"].to_string(),
+            }
+        }
+        Value::Object(map) => {
+            map.iter()
+                .for_each(|(key, value)| flatten(&child(key), value, details));
+            return;
+        }
+        Value::Array(items) if items.iter().any(|i| i.is_object() || i.is_array()) => {
+            items
+                .iter()
+                .enumerate()
+                .for_each(|(i, item)| flatten(&child(&i.to_string()), item, details));
+            return;
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|item| item.as_str().map_or(item.to_string(), |s| s.to_string()))
+            .collect::<Vec<_>>()
+            .join(","),
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    details.push((name.to_string(), date_or_text(name, text)));
+}
+
+/// Timestamps of the `time` fields, shown as local dates
+fn date_or_text(name: &str, text: String) -> String {
+    let is_date = name
+        .strip_prefix("time.")
+        .is_some_and(|field| ["submission", "eligible", "start", "end"].contains(&field));
+    match text.parse::<i64>() {
+        Result::Ok(timestamp) if is_date && timestamp > 0 => DateTime::from_timestamp(timestamp, 0)
+            .map_or(text, |d| d.with_timezone(&Local).naive_local().to_string()),
+        _ => text,
+    }
+}
+
+/// Plain numbers were replaced by `{"set": true, "infinite": false, "number": 12}`
+/// in recent slurm versions
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonNumber {
+    Plain(u64),
+    Struct {
+        #[serde(default)]
+        set: Option<bool>,
+        #[serde(default)]
+        number: Option<u64>,
+    },
+}
+
+impl JsonNumber {
+    fn value(&self) -> Option<u64> {
+        match self {
+            JsonNumber::Plain(n) => Some(*n),
+            JsonNumber::Struct { set, number } => match set {
+                Some(false) => None,
+                _ => *number,
+            },
+        }
+    }
+}
+
+/// Plain number, or the number of a `{set, infinite, number}` object
+fn json_u64(value: &serde_json::Value) -> Option<u64> {
+    value.as_u64().or_else(|| value["number"].as_u64())
+}
+
+fn number(n: &Option<JsonNumber>) -> Option<u64> {
+    n.as_ref().and_then(JsonNumber::value)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrList {
+    String(String),
+    List(Vec<String>),
+}
+
+impl StringOrList {
+    fn join(&self) -> String {
+        match self {
+            StringOrList::String(s) => s.clone(),
+            StringOrList::List(l) => l.join(" "),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonState {
+    current: Option<StringOrList>,
+    reason: Option<String>,
+}
+
+/// Older versions give the state as a plain string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StateField {
+    Plain(StringOrList),
+    Struct(JsonState),
+}
+
+impl StateField {
+    fn state_str(&self) -> String {
+        match self {
+            StateField::Plain(s) => s.join(),
+            StateField::Struct(s) => s.current.as_ref().map(|c| c.join()).unwrap_or_default(),
+        }
+    }
+
+    fn reason(&self) -> String {
+        match self {
+            StateField::Plain(_) => String::new(),
+            StateField::Struct(s) => s.reason.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonSignal {
+    id: Option<JsonNumber>,
+    signal_id: Option<JsonNumber>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonExitCode {
+    return_code: Option<JsonNumber>,
+    signal: Option<JsonSignal>,
+}
+
+impl JsonExitCode {
+    /// Same `return_code:signal` format as the text output
+    fn format(&self) -> String {
+        let signal = self
+            .signal
+            .as_ref()
+            .and_then(|s| number(&s.id).or(number(&s.signal_id)))
+            .unwrap_or(0);
+        format!("{}:{}", number(&self.return_code).unwrap_or(0), signal)
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonTotal {
+    seconds: u64,
+    microseconds: u64,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonTime {
+    submission: Option<i64>,
+    elapsed: Option<u64>,
+    limit: Option<JsonNumber>,
+    total: Option<JsonTotal>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonTres {
+    #[serde(rename = "type")]
+    tres_type: String,
+    count: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonTresUsage {
+    max: Vec<JsonTres>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonTresGroup {
+    allocated: Vec<JsonTres>,
+    requested: Option<JsonTresUsage>,
+}
+
+fn tres_count(tres: &[JsonTres], tres_type: &str) -> Option<u64> {
+    tres.iter()
+        .find(|t| t.tres_type == tres_type)
+        .and_then(|t| t.count)
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonRequired {
+    #[serde(rename = "CPUs")]
+    cpus: Option<u64>,
+    memory_per_node: Option<JsonNumber>,
+    memory_per_cpu: Option<JsonNumber>,
+    memory: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonArray {
+    job_id: Option<u64>,
+    task_id: Option<JsonNumber>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonHet {
+    job_id: Option<u64>,
+    job_offset: Option<JsonNumber>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StepId {
+    Plain(String),
+    Struct {
+        job_id: u64,
+        step_id: serde_json::Value,
+    },
+}
+
+#[derive(Deserialize)]
+struct JsonStepInfo {
+    id: StepId,
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct JsonStep {
+    step: JsonStepInfo,
+    state: Option<StateField>,
+    #[serde(default)]
+    exit_code: JsonExitCode,
+    #[serde(default)]
+    time: JsonTime,
+    #[serde(default)]
+    tres: JsonTresGroup,
+}
+
+#[derive(Deserialize)]
+struct JsonJob {
+    job_id: u64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    partition: String,
+    #[serde(default)]
+    account: String,
+    state: Option<StateField>,
+    #[serde(default)]
+    exit_code: JsonExitCode,
+    #[serde(default)]
+    submit_line: String,
+    #[serde(default)]
+    working_directory: String,
+    #[serde(default)]
+    time: JsonTime,
+    #[serde(default)]
+    required: JsonRequired,
+    #[serde(default)]
+    tres: JsonTresGroup,
+    #[serde(default)]
+    array: JsonArray,
+    #[serde(default)]
+    het: JsonHet,
+    #[serde(default)]
+    steps: Vec<JsonStep>,
+}
+
+impl JsonJob {
+    /// Job id as printed by the text output: `12_3` for array tasks, `12+1` for het components
+    fn display_id(&self) -> String {
+        if let (Some(array_id), Some(task_id)) = (self.array.job_id, number(&self.array.task_id)) {
+            if array_id != 0 {
+                return format!("{}_{}", array_id, task_id);
+            }
+        }
+        if let (Some(het_id), Some(offset)) = (self.het.job_id, number(&self.het.job_offset)) {
+            if het_id != 0 {
+                return format!("{}+{}", het_id, offset);
+            }
+        }
+        self.job_id.to_string()
+    }
+
+    fn alloc_cpus(&self) -> usize {
+        tres_count(&self.tres.allocated, "cpu")
+            .or(self.required.cpus)
+            .unwrap_or(0) as usize
+    }
+
+    /// Requested memory in kilobytes
+    fn reqmem(&self) -> usize {
+        let megabytes = if let Some(per_node) = number(&self.required.memory_per_node) {
+            per_node
+        } else if let Some(per_cpu) = number(&self.required.memory_per_cpu) {
+            per_cpu * self.alloc_cpus() as u64
+        } else {
+            self.required.memory.unwrap_or(0)
+        };
+        (megabytes << 10) as usize
+    }
+}
+
+fn submit_date(time: &JsonTime) -> Option<NaiveDateTime> {
+    // sacct prints dates in local time
+    time.submission
+        .and_then(|s| DateTime::from_timestamp(s, 0))
+        .map(|d| d.with_timezone(&Local).naive_local())
+}
+
+fn total_cpu(time: &JsonTime) -> usize {
+    time.total
+        .as_ref()
+        .map(|t| t.seconds + t.microseconds / 1_000_000)
+        .unwrap_or(0) as usize
+}
+
+fn state(state: &Option<StateField>) -> JobState {
+    let state_str = state.as_ref().map(|s| s.state_str()).unwrap_or_default();
+    JobState::from_str(&state_str)
+}
+
+/// Name of the step after the job id, like `batch` or `0`
+fn step_name(step: &JsonStep) -> String {
+    match step.step.id {
+        StepId::Plain(ref id) => id
+            .split_once('.')
+            .map_or(id.clone(), |(_, s)| s.to_string()),
+        StepId::Struct { ref step_id, .. } => match step_id {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        },
+    }
+}
+
+fn step_fields(job: &JsonJob, job_row: &JobFields, step: &JsonStep) -> JobFields {
+    let job_id = match step.step.id {
+        StepId::Plain(ref id) => id.clone(),
+        StepId::Struct { job_id, .. } => {
+            // the step belongs to the job as displayed (array task, het component)
+            let parent = if job_id == job.job_id {
+                job_row.job_id.clone()
+            } else {
+                job_id.to_string()
+            };
+            format!("{}.{}", parent, step_name(step))
+        }
+    };
+    // tres counts are in bytes
+    let maxrss = step
+        .tres
+        .requested
+        .as_ref()
+        .and_then(|r| tres_count(&r.max, "mem"))
+        .map(|bytes| bytes >> 10)
+        .unwrap_or(0);
+    let alloc_cpus = match tres_count(&step.tres.allocated, "cpu") {
+        Some(cpus) => NumberOrCol::Value(cpus as usize),
+        None => job_row.alloc_cpus.clone(),
+    };
+    JobFields {
+        job_id,
+        job_name: step.step.name.clone(),
+        partition: String::new(),
+        account: job_row.account.clone(),
+        alloc_cpus,
+        state: state(&step.state),
+        exit_code: step.exit_code.format(),
+        submit_line: String::new(),
+        workdir: job_row.workdir.clone(),
+        submit: job_row.submit,
+        reqmem: NumberOrCol::Value(0),
+        maxrss: NumberOrCol::Value(maxrss as usize),
+        elapsed: NumberOrCol::Value(step.time.elapsed.unwrap_or(0) as usize),
+        time_limit: NumberOrCol::Value(0),
+        cpu_time_raw: NumberOrCol::Value(total_cpu(&step.time)),
+        reason: String::new(),
+        job_id_raw: format!("{}.{}", job.job_id, step_name(step)),
+        group_summary: None,
+    }
+}
+
+fn job_fields(job: &JsonJob) -> JobFields {
+    JobFields {
+        job_id: job.display_id(),
+        job_name: job.name.clone(),
+        partition: job.partition.clone(),
+        account: job.account.clone(),
+        alloc_cpus: NumberOrCol::Value(job.alloc_cpus()),
+        state: state(&job.state),
+        exit_code: job.exit_code.format(),
+        submit_line: job.submit_line.clone(),
+        workdir: job.working_directory.clone(),
+        submit: submit_date(&job.time),
+        reqmem: NumberOrCol::Value(job.reqmem()),
+        maxrss: NumberOrCol::Value(0),
+        elapsed: NumberOrCol::Value(job.time.elapsed.unwrap_or(0) as usize),
+        time_limit: NumberOrCol::Value(number(&job.time.limit).unwrap_or(0) as usize),
+        cpu_time_raw: NumberOrCol::Value(total_cpu(&job.time)),
+        reason: job.state.as_ref().map(|s| s.reason()).unwrap_or_default(),
+        job_id_raw: job.job_id.to_string(),
+        group_summary: None,
+    }
+}
+
+impl JobFields {
+    /// Rows in the same layout as `from_sacct_str`, header line included
+    pub fn from_sacct_json(sacct_res: &str) -> Result<Vec<JobFields>> {
+        let sacct_json: SacctJson = serde_json::from_str(sacct_res)?;
+        first_error(&sacct_json.errors)?;
+        let mut all_fields = vec![JobFields::header()?];
+        for job in &sacct_json.jobs {
+            let job_row = job_fields(job);
+            let steps: Vec<_> = job
+                .steps
+                .iter()
+                .map(|step| step_fields(job, &job_row, step))
+                .collect();
+            all_fields.push(job_row);
+            all_fields.extend(steps);
+        }
+        Ok(all_fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::job_handler::{self, SacctFormat};
+    use crate::jobs::job_source::{CommandRunner, TimeBound, TimeWindow};
+    use std::cell::{Cell, RefCell};
+    use std::process::{Command, Output};
+
+    fn recorded(data_parser: &str) -> Vec<JobFields> {
+        let path = format!("test_data/sacct_json/{}.json", data_parser);
+        JobFields::from_sacct_json(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn row<'a>(rows: &'a [JobFields], job_id: &str) -> &'a JobFields {
+        rows.iter().find(|row| row.job_id == job_id).unwrap()
+    }
+
+    fn local_date(timestamp: i64) -> Option<NaiveDateTime> {
+        DateTime::from_timestamp(timestamp, 0).map(|d| d.with_timezone(&Local).naive_local())
+    }
+
+    #[test]
+    fn plain_numbers_and_states() {
+        let rows = recorded("v0.0.37");
+        let ids: Vec<&str> = rows.iter().map(|row| row.job_id.as_str()).collect();
+        assert_eq!(ids, ["JobID", "3001", "3001.batch", "3010_2", "3020+1"]);
+        let job = row(&rows, "3001");
+        assert_eq!(job.state, JobState::Completed);
+        assert_eq!(job.reason, "None");
+        assert_eq!(job.alloc_cpus.clone().take(), Some(4));
+        assert_eq!(job.reqmem.clone().take(), Some(8192 << 10));
+        assert_eq!(job.time_limit.clone().take(), Some(60));
+        assert_eq!(job.cpu_time_raw.clone().take(), Some(1500));
+        assert_eq!(job.submit, local_date(1700000000));
+        let step = row(&rows, "3001.batch");
+        assert_eq!(step.state, JobState::Completed);
+        assert_eq!(step.maxrss.clone().take(), Some(3221225472 >> 10));
+        assert_eq!(step.exit_code, "0:0");
+        assert_eq!(row(&rows, "3010_2").exit_code, "1:0");
+    }
+
+    #[test]
+    fn set_numbers_and_state_lists() {
+        let rows = recorded("v0.0.39");
+        let ids: Vec<&str> = rows.iter().map(|row| row.job_id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "JobID",
+                "4001",
+                "4001.batch",
+                "4010_7",
+                "4010_7.0",
+                "4020+1",
+                "4020+1.batch"
+            ]
+        );
+        let job = row(&rows, "4001");
+        assert_eq!(job.state, JobState::Running);
+        assert_eq!(job.reason, "None");
+        // memory per cpu, a limit that is not set
+        assert_eq!(job.reqmem.clone().take(), Some((8 * 2048) << 10));
+        assert_eq!(job.time_limit.clone().take(), Some(0));
+        assert_eq!(job.submit, local_date(1710000000));
+        assert_eq!(
+            row(&rows, "4001.batch").maxrss.clone().take(),
+            Some(1048576)
+        );
+        let task = row(&rows, "4010_7");
+        assert_eq!(task.state, JobState::Cancelled("OUT_OF_MEMORY".to_string()));
+        assert_eq!(task.reqmem.clone().take(), Some(4096 << 10));
+        assert_eq!(task.exit_code, "0:9");
+        assert_eq!(task.job_id_raw, "4017");
+        let step = row(&rows, "4010_7.0");
+        assert_eq!(step.job_id_raw, "4017.0");
+        assert_eq!(step.job_name, "python");
+        assert_eq!(step.maxrss.clone().take(), Some(4194304));
+        assert_eq!(
+            row(&rows, "4020+1.batch").alloc_cpus.clone().take(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn details_of_array_tasks_and_het_components() {
+        let answer = std::fs::read_to_string("test_data/sacct_json/v0.0.39.json").unwrap();
+        let name = |job_id: &str| {
+            let details = job_details(&answer, job_id).unwrap();
+            let (_, value) = details
+                .into_iter()
+                .find(|(field, _)| field == "name")
+                .unwrap();
+            value
+        };
+        assert_eq!(name("4001"), "train");
+        assert_eq!(name("4010"), "sweep");
+        assert_eq!(name("4020+1"), "pipeline");
+        assert!(job_details(&answer, "5000").is_err());
+    }
+
+    #[test]
+    fn slurm_errors() {
+        let answer =
+            r#"{"jobs": [], "errors": [{"error": "Invalid user", "description": "jdoe"}]}"#;
+        let error = JobFields::from_sacct_json(answer).unwrap_err();
+        assert_eq!(error.to_string(), "slurm error: Invalid user jdoe");
+    }
+
+    /// sacct of a slurm too old for `--json`, recording the calls
+    struct OldSacct {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl CommandRunner for OldSacct {
+        fn output(&self, _cmd: &str, command_args: &[&str]) -> Result<Output> {
+            let json = command_args.contains(&"--json");
+            self.calls.borrow_mut().push(command_args.join(" "));
+            let script = match json {
+                true => "echo \"sacct: unrecognized option '--json'\" >&2; exit 1",
+                false => "cat test_data/fixture/sacct.txt",
+            };
+            Ok(Command::new("sh").args(["-c", script]).output()?)
+        }
+    }
+
+    #[test]
+    fn auto_format_falls_back_to_text() {
+        let sacct = OldSacct {
+            calls: RefCell::new(vec![]),
+        };
+        let window = TimeWindow {
+            start: TimeBound::HoursAgo(24),
+            end: None,
+        };
+        let format = Cell::new(SacctFormat::Auto);
+        let rows = job_handler::list_jobs_sacct(&sacct, &window, &format).unwrap();
+        assert_eq!(rows[0].job_id, "JobID");
+        assert_eq!(rows[1].job_id, "1001");
+        assert!(format.get() == SacctFormat::Text);
+        // the format is remembered, so json is not tried again
+        job_handler::list_jobs_sacct(&sacct, &window, &format).unwrap();
+        let calls = sacct.calls.borrow();
+        let json_calls = calls.iter().filter(|call| call.contains("--json")).count();
+        assert_eq!((calls.len(), json_calls), (3, 1));
+    }
+}
@@ -0,0 +1,30 @@
+//! Quoting of the arguments of commands run through a shell, like the remote
+//! shell of ssh or the `cd <workdir> && sbatch ...` of a resubmission
+
+/// The argument in single quotes, read back by sh as the exact same word
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn quoted_words_reach_the_command_unchanged() {
+        for arg in [
+            "it's",
+            "two words",
+            "$HOME and $(id)",
+            "`id`",
+            "a\\b\"c",
+            "",
+        ] {
+            let script = format!("printf %s {}", shell_quote(arg));
+            let output = Command::new("sh").args(["-c", &script]).output().unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), arg);
+        }
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
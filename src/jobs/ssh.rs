@@ -0,0 +1,269 @@
+use super::job_action::JobAction;
+use super::job_handler;
+use super::job_handler::SacctFormat;
+use super::job_parser::JobFields;
+use super::job_source::{CommandRunner, JobDetails, JobSource, TimeWindow};
+use super::log_page::{FileSearch, LogPage, PageRange};
+use super::log_paths::LogSearch;
+use super::shell::shell_quote;
+use crate::config::{LogConfig, SshConfig};
+use crate::parser::SshArgs;
+use color_eyre::eyre::{Report, Result};
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use tracing::info;
+
+static DEFAULT_CONTROL_PERSIST: &str = "10m";
+
+#[derive(Clone, Debug)]
+pub struct SshTarget {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_file: Option<PathBuf>,
+    pub proxy_jump: Option<String>,
+    pub options: Vec<String>,
+    pub control_master: bool,
+    pub control_persist: String,
+}
+
+impl SshTarget {
+    /// Builds the target from the command line, falling back on the config file
+    pub fn new(args: &SshArgs, config: &SshConfig) -> Result<Self> {
+        let host = args
+            .host
+            .clone()
+            .or(config.host.clone())
+            .ok_or(Report::msg(
+                "ssh run mode needs a host: use --host or set `host` in the [ssh] config section",
+            ))?;
+        let mut options = config.options.clone();
+        options.extend(args.ssh_options.iter().cloned());
+        Ok(SshTarget {
+            host,
+            user: args.user.clone().or(config.user.clone()),
+            port: args.port.or(config.port),
+            identity_file: args.identity_file.clone().or(config.identity_file.clone()),
+            proxy_jump: args.proxy_jump.clone().or(config.proxy_jump.clone()),
+            options,
+            control_master: !args.no_control_master && config.control_master.unwrap_or(true),
+            control_persist: config
+                .control_persist
+                .clone()
+                .unwrap_or(DEFAULT_CONTROL_PERSIST.to_string()),
+        })
+    }
+
+    fn destination(&self) -> String {
+        match self.user {
+            Some(ref user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        }
+    }
+
+    fn control_path() -> String {
+        // %C is a hash of the connection, which keeps the socket path short
+        std::env::temp_dir()
+            .join("sboard-ssh-%C")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// ssh invocation with every connection option, without the remote command
+    fn base_command(&self) -> Command {
+        let mut command = Command::new("ssh");
+        // never prompt: a password prompt would hang the TUI
+        command.args(["-o", "BatchMode=yes"]);
+        if let Some(port) = self.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        if let Some(ref identity_file) = self.identity_file {
+            command.arg("-i").arg(identity_file);
+        }
+        if let Some(ref proxy_jump) = self.proxy_jump {
+            command.arg("-J").arg(proxy_jump);
+        }
+        if self.control_master {
+            command
+                .arg("-o")
+                .arg("ControlMaster=auto")
+                .arg("-o")
+                .arg(format!("ControlPath={}", Self::control_path()))
+                .arg("-o")
+                .arg(format!("ControlPersist={}", self.control_persist));
+        }
+        for option in &self.options {
+            command.arg("-o").arg(option);
+        }
+        command
+    }
+
+    pub fn command(&self, cmd: &str, command_args: &[&str]) -> Command {
+        let mut command = self.base_command();
+        // the remote side runs the command through a shell, so every word is quoted
+        command
+            .arg(self.destination())
+            .arg(cmd)
+            .args(command_args.iter().map(|arg| shell_quote(arg)));
+        command
+    }
+
+    /// Stops the shared master connection, if any
+    pub fn close(&self) {
+        if !self.control_master {
+            return;
+        }
+        let res = self
+            .base_command()
+            .arg("-O")
+            .arg("exit")
+            .arg(self.destination())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        info!("closing ssh master: {:?}", res);
+    }
+}
+
+/// Runs commands on a remote host through ssh
+pub struct SshSource {
+    target: SshTarget,
+    sacct_format: Cell<SacctFormat>,
+    log_search: LogSearch,
+}
+
+impl SshSource {
+    pub fn new(
+        args: &SshArgs,
+        config: &SshConfig,
+        log_config: &LogConfig,
+        sacct_format: SacctFormat,
+    ) -> Result<Self> {
+        Ok(SshSource {
+            target: SshTarget::new(args, config)?,
+            sacct_format: Cell::new(sacct_format),
+            log_search: LogSearch::new(log_config),
+        })
+    }
+}
+
+impl CommandRunner for SshSource {
+    fn output(&self, cmd: &str, command_args: &[&str]) -> Result<Output> {
+        Ok(self.target.command(cmd, command_args).output()?)
+    }
+}
+
+impl JobSource for SshSource {
+    fn list_jobs(&self, window: &TimeWindow) -> Result<Vec<JobFields>> {
+        job_handler::list_jobs_sacct(self, window, &self.sacct_format)
+    }
+    fn list_logs(&self, job: &JobFields) -> Result<Vec<String>> {
+        job_handler::list_logs(self, job, &self.log_search)
+    }
+    fn read_log(&self, path: &str) -> Result<String> {
+        job_handler::read_file(self, path)
+    }
+    fn read_log_page(&self, path: &str, range: PageRange) -> Result<LogPage> {
+        job_handler::read_file_page(self, path, range)
+    }
+    fn search_log(&self, path: &str, search: &FileSearch) -> Result<Option<u64>> {
+        job_handler::search_file(self, path, search)
+    }
+    fn list_scripts(&self, dir: &str) -> Result<Vec<String>> {
+        job_handler::find_scripts(self, dir)
+    }
+    fn job_details(&self, job_id: &str) -> Result<JobDetails> {
+        job_handler::job_details(self, job_id)
+    }
+    fn run_action(&self, action: &JobAction) -> Result<String> {
+        job_handler::run_action(self, action)
+    }
+    fn close(&self) {
+        self.target.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Cli;
+    use clap::Parser;
+
+    fn target(args: &[&str], config: &str) -> SshTarget {
+        let cli = Cli::try_parse_from(["sboard", "--run-mode", "ssh"].iter().chain(args)).unwrap();
+        let config: SshConfig = toml::from_str(config).unwrap();
+        SshTarget::new(&cli.ssh, &config).unwrap()
+    }
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn connection_options() {
+        let target = target(
+            &[
+                "--host",
+                "login1",
+                "--user",
+                "jdoe",
+                "--port",
+                "2222",
+                "--identity-file",
+                "/home/jdoe/.ssh/cluster",
+                "--proxy-jump",
+                "gateway",
+                "--ssh-option",
+                "Compression=yes",
+            ],
+            "options = [\"ServerAliveInterval=30\"]\ncontrol_persist = \"1h\"",
+        );
+        let control_path = format!("ControlPath={}", SshTarget::control_path());
+        assert!(control_path.ends_with("sboard-ssh-%C"));
+        assert_eq!(
+            args(&target.command("sacct", &["-j", "12 13"])),
+            [
+                "-o",
+                "BatchMode=yes",
+                "-p",
+                "2222",
+                "-i",
+                "/home/jdoe/.ssh/cluster",
+                "-J",
+                "gateway",
+                "-o",
+                "ControlMaster=auto",
+                "-o",
+                &control_path,
+                "-o",
+                "ControlPersist=1h",
+                "-o",
+                "ServerAliveInterval=30",
+                "-o",
+                "Compression=yes",
+                "jdoe@login1",
+                "sacct",
+                "'-j'",
+                "'12 13'",
+            ]
+        );
+    }
+
+    #[test]
+    fn command_line_overrides_the_config() {
+        let config = "host = \"login1\"\nuser = \"jdoe\"\nport = 22";
+        let target = target(&["--host", "login2", "--no-control-master"], config);
+        assert_eq!(target.destination(), "jdoe@login2");
+        assert_eq!(
+            args(&target.command("squeue", &[])),
+            ["-o", "BatchMode=yes", "-p", "22", "jdoe@login2", "squeue"]
+        );
+        let config: SshConfig = toml::from_str("control_master = false").unwrap();
+        let cli = Cli::try_parse_from(["sboard"]).unwrap();
+        assert!(SshTarget::new(&cli.ssh, &config).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Colours of the log lines telling why a job failed
+use crate::config::LogConfig;
+use color_eyre::eyre::{Report, Result};
+use ratatui::style::{Color, Style};
+use regex::Regex;
+use std::str::FromStr;
+
+/// Failures written by python, the kernel and slurm, then any error or warning
+static DEFAULT_HIGHLIGHTS: [(&str, Color); 8] = [
+    (r"^Traceback \(most recent call last\):", Color::Red),
+    (
+        r"^\w+(\.\w+)*(Error|Exception|Interrupt|Exit)(: |$)",
+        Color::Red,
+    ),
+    (r"Segmentation fault", Color::Red),
+    (r"(?i)oom[-_ ]kill", Color::Red),
+    (r"slurmstepd: error", Color::Red),
+    (r"CANCELLED AT .* DUE TO TIME LIMIT", Color::Red),
+    (r"\b(Error|ERROR|error)\b", Color::LightRed),
+    (r"\b(WARN|WARNING|Warning|warning)\b", Color::Yellow),
+];
+
+#[derive(Clone, Debug)]
+struct HighlightRule {
+    regex: Regex,
+    color: Color,
+}
+
+/// Rules colouring the log lines, the first matching one winning
+#[derive(Clone, Debug)]
+pub struct LogHighlighter {
+    rules: Vec<HighlightRule>,
+}
+
+impl LogHighlighter {
+    /// Configured rules, followed by the default ones unless turned off
+    pub fn new(config: &LogConfig) -> Result<Self> {
+        let mut rules = vec![];
+        for highlight in &config.highlights {
+            let regex = Regex::new(&highlight.pattern).map_err(|e| {
+                Report::msg(format!(
+                    "Invalid highlight pattern {}: {}",
+                    highlight.pattern, e
+                ))
+            })?;
+            let color = Color::from_str(&highlight.color).map_err(|_| {
+                Report::msg(format!("Invalid highlight color: {}", highlight.color))
+            })?;
+            rules.push(HighlightRule { regex, color });
+        }
+        if config.default_highlights.unwrap_or(true) {
+            for (pattern, color) in DEFAULT_HIGHLIGHTS {
+                rules.push(HighlightRule {
+                    regex: Regex::new(pattern).expect("default highlight patterns are valid"),
+                    color,
+                });
+            }
+        }
+        Ok(LogHighlighter { rules })
+    }
+
+    /// Style of the line, none when no rule matches it
+    pub fn style(&self, line: &str) -> Option<Style> {
+        self.rules
+            .iter()
+            .find(|rule| rule.regex.is_match(line))
+            .map(|rule| Style::default().fg(rule.color))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_config(toml: &str) -> LogConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn color(highlighter: &LogHighlighter, line: &str) -> Option<Color> {
+        highlighter.style(line).and_then(|style| style.fg)
+    }
+
+    #[test]
+    fn default_rules() {
+        let highlighter = LogHighlighter::new(&LogConfig::default()).unwrap();
+        let lines = [
+            ("Traceback (most recent call last):", Some(Color::Red)),
+            ("ValueError: x", Some(Color::Red)),
+            ("torch.cuda.OutOfMemoryError: CUDA out of memory.", Some(Color::Red)),
+            ("KeyboardInterrupt", Some(Color::Red)),
+            (
+                "slurmstepd: error: Detected 1 oom-kill event(s) in StepId=1234.batch.",
+                Some(Color::Red),
+            ),
+            (
+                "slurmstepd: error: *** JOB 1234 ON node01 CANCELLED AT 2024-03-12T10:00:00 DUE TO TIME LIMIT ***",
+                Some(Color::Red),
+            ),
+            ("/var/spool/slurmd/job1234/slurm_script: line 12: 4321 Segmentation fault", Some(Color::Red)),
+            ("ERROR: could not open data/train.csv", Some(Color::LightRed)),
+            ("WARNING: the learning rate is high", Some(Color::Yellow)),
+            ("UserWarning: warning about nothing", Some(Color::Yellow)),
+            ("epoch 3: loss 0.21, no errors", None),
+            ("  File \"train.py\", line 3, in <module>", None),
+        ];
+        for (line, expected) in lines {
+            assert_eq!(color(&highlighter, line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn configured_rules_win_over_the_defaults() {
+        let config = log_config(
+            r##"
+            [[highlights]]
+            pattern = "^ValueError"
+            color = "blue"
+            [[highlights]]
+            pattern = "loss"
+            color = "#00ff00"
+            "##,
+        );
+        let highlighter = LogHighlighter::new(&config).unwrap();
+        assert_eq!(color(&highlighter, "ValueError: x"), Some(Color::Blue));
+        assert_eq!(
+            color(&highlighter, "epoch 3: loss 0.21"),
+            Some(Color::Rgb(0, 255, 0))
+        );
+        assert_eq!(color(&highlighter, "KeyError: 'a'"), Some(Color::Red));
+        let config = log_config(
+            r#"
+            default_highlights = false
+            [[highlights]]
+            pattern = "loss"
+            color = "cyan"
+            "#,
+        );
+        let highlighter = LogHighlighter::new(&config).unwrap();
+        assert_eq!(color(&highlighter, "ValueError: x"), None);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let error = |toml: &str| {
+            LogHighlighter::new(&log_config(toml))
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error("[[highlights]]\npattern = \"(\"\ncolor = \"red\"")
+            .starts_with("Invalid highlight pattern ("));
+        assert_eq!(
+            error("[[highlights]]\npattern = \"x\"\ncolor = \"reddish\""),
+            "Invalid highlight color: reddish"
+        );
+        // a rule without a pattern would colour every line
+        assert!(toml::from_str::<LogConfig>("[[highlights]]\ncolor = \"red\"").is_err());
+        assert!(toml::from_str::<LogConfig>("[[highlights]]\npattern = \"x\"").is_err());
+    }
+}
@@ -1,11 +1,18 @@
+mod ansi;
 mod app;
+mod config;
 mod editor;
 mod job_query_info;
 mod jobs;
+mod log_highlight;
 mod parser;
+mod prompt;
+mod submit;
 mod ui;
+mod worker;
 
 use crate::app::App;
+use crate::config::Config;
 use better_panic::Settings;
 use clap::Parser;
 use color_eyre::eyre::Result;
@@ -21,6 +28,11 @@ use ui::ui;
 
 pub fn initialize_panic_handler() {
     std::panic::set_hook(Box::new(|panic_info| {
+        // the worker catches its panics, shown as errors of its requests
+        if std::thread::current().name() == Some(worker::THREAD_NAME) {
+            info!("worker panicked: {}", panic_info);
+            return;
+        }
         crossterm::execute!(std::io::stderr(), crossterm::terminal::LeaveAlternateScreen).unwrap();
         crossterm::terminal::disable_raw_mode().unwrap();
         Settings::auto()
@@ -35,10 +47,12 @@ fn main() -> Result<()> {
     initialize_panic_handler();
     setup_logging();
     let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
+    let mut app = App::new(cli, config)?;
     let mut terminal = setup_terminal()?;
-    let mut app = App::new(cli);
     let run_result = run(&mut terminal, &mut app);
     restore_terminal(&mut terminal)?;
+    app.worker.close();
     run_result?;
     Ok(())
 }
@@ -71,7 +85,9 @@ fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result
 
 fn run(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App) -> Result<()> {
     loop {
-        app.fetch_jobs()?;
+        app.fetch_jobs();
+        app.fetch_log_page();
+        app.receive();
         terminal.draw(|frame| {
             ui(frame, app);
         })?;
@@ -1,10 +1,15 @@
-use crate::jobs::job_handler::DisplayMode;
-use clap::{Parser, ValueEnum};
+use crate::jobs::job_handler::{DisplayMode, SacctFormat};
+use crate::jobs::job_source::TimeBound;
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use clap::{Args, Parser, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum RunMode {
     Slurm,
     FromFile,
     Ssh,
+    Rest,
 }
 #[derive(Parser)]
 pub struct Cli {
@@ -12,8 +17,195 @@ pub struct Cli {
     pub run_mode: RunMode,
     #[arg(long)]
     pub refresh: bool,
+    /// Time between two refreshes, e.g. 30s or 2m (default: 30s)
+    #[arg(long, value_parser = parse_duration)]
+    pub refresh_interval: Option<Duration>,
+    /// Minimum time between two calls to sacct, whatever triggers them (default: 10s)
+    #[arg(long, value_parser = parse_duration)]
+    pub min_refresh_interval: Option<Duration>,
     #[arg(short, long, value_enum, default_value_t = DisplayMode::Cpu)]
     pub display_mode: DisplayMode,
     #[arg(short, long, value_parser = clap::value_parser!(u16).range(10..), default_value_t = 30)]
     pub job_max_display: u16,
+    /// Output format requested from sacct
+    #[arg(long, value_enum, default_value_t = SacctFormat::Auto)]
+    pub sacct_format: SacctFormat,
+    /// Start of the time window: a date (2024-03-01, 2024-03-01T12:00), a time ago (12h, 3d, 2w),
+    /// today or yesterday
+    #[arg(long, value_parser = parse_time_bound)]
+    pub since: Option<TimeBound>,
+    /// End of the time window, in the same formats as --since (default: now)
+    #[arg(long, value_parser = parse_time_bound, requires = "since")]
+    pub until: Option<TimeBound>,
+    /// Read jobs and logs from a recorded fixture directory (implies --run-mode from-file)
+    #[arg(long, value_name = "DIR")]
+    pub fixture: Option<PathBuf>,
+    /// Path to the config file (default: ~/.config/sboard/config.toml)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    #[command(flatten)]
+    pub ssh: SshArgs,
+    /// Base url of slurmrestd for `--run-mode rest`, e.g. http://localhost:6820
+    #[arg(long)]
+    pub rest_url: Option<String>,
+}
+
+/// Connection options for `--run-mode ssh`, overriding the `[ssh]` config section
+#[derive(Args)]
+pub struct SshArgs {
+    /// Host to run slurm commands on
+    #[arg(long)]
+    pub host: Option<String>,
+    /// Remote user name
+    #[arg(long)]
+    pub user: Option<String>,
+    /// Remote ssh port
+    #[arg(long)]
+    pub port: Option<u16>,
+    /// Identity file passed to ssh -i
+    #[arg(long)]
+    pub identity_file: Option<PathBuf>,
+    /// Jump host passed to ssh -J
+    #[arg(long)]
+    pub proxy_jump: Option<String>,
+    /// Extra ssh option, as for ssh -o (can be repeated)
+    #[arg(long = "ssh-option", value_name = "OPTION")]
+    pub ssh_options: Vec<String>,
+    /// Open a new ssh connection for every command instead of sharing one
+    #[arg(long)]
+    pub no_control_master: bool,
+}
+
+/// Parses durations like `90`, `90s`, `2m` or `1h`, in seconds when there is no unit
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (num_str, multiplier) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 3600),
+        _ => (s, 1),
+    };
+    let invalid = || format!("invalid duration `{}`, expected e.g. 30s, 2m or 1h", s);
+    let num: u64 = num_str.trim().parse().map_err(|_| invalid())?;
+    let secs = num
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("duration `{}` is too long", s))?;
+    Ok(Duration::from_secs(secs))
+}
+
+static DATE_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Parses a date, a time ago like `12h`, `3d` or `2w`, `now`, `today` or `yesterday`
+pub fn parse_time_bound(s: &str) -> Result<TimeBound, String> {
+    let s = s.trim();
+    let today = Local::now().date_naive().and_time(NaiveTime::MIN);
+    match s {
+        "now" => return Ok(TimeBound::HoursAgo(0)),
+        "today" => return Ok(TimeBound::At(today)),
+        "yesterday" => return Ok(TimeBound::At(today - chrono::Duration::days(1))),
+        _ => (),
+    }
+    if let Some((i, unit)) = s.char_indices().last() {
+        let hours_per_unit = match unit {
+            'h' => Some(1),
+            'd' => Some(24),
+            'w' => Some(24 * 7),
+            _ => None,
+        };
+        if let (Some(hours_per_unit), Ok(num)) = (hours_per_unit, s[..i].parse::<u32>()) {
+            return num
+                .checked_mul(hours_per_unit)
+                .map(TimeBound::HoursAgo)
+                .ok_or(format!("time `{}` is too long ago", s));
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(TimeBound::At(date.and_time(NaiveTime::MIN)));
+    }
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .map(TimeBound::At)
+        .ok_or(format!(
+            "invalid time `{}`, expected e.g. 2024-03-01, 2024-03-01T12:00, 12h, 3d, 2w or yesterday",
+            s
+        ))
+}
+
+/// Checks that a time window does not end before it starts
+pub fn check_time_window(start: TimeBound, end: Option<TimeBound>) -> Result<(), String> {
+    match end {
+        Some(end) if end.timestamp() < start.timestamp() => Err(format!(
+            "the time window ends ({}) before it starts ({})",
+            end.display(),
+            start.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("2x").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert_eq!(
+            parse_duration("18446744073709551615h"),
+            Err("duration `18446744073709551615h` is too long".to_string())
+        );
+    }
+
+    #[test]
+    fn time_bounds() {
+        let at = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").unwrap();
+        assert_eq!(parse_time_bound("now"), Ok(TimeBound::HoursAgo(0)));
+        assert_eq!(parse_time_bound("12h"), Ok(TimeBound::HoursAgo(12)));
+        assert_eq!(parse_time_bound("3d"), Ok(TimeBound::HoursAgo(72)));
+        assert_eq!(parse_time_bound(" 2w "), Ok(TimeBound::HoursAgo(336)));
+        assert_eq!(
+            parse_time_bound("2024-03-01"),
+            Ok(TimeBound::At(at("2024-03-01T00:00:00")))
+        );
+        assert_eq!(
+            parse_time_bound("2024-03-01T12:30"),
+            Ok(TimeBound::At(at("2024-03-01T12:30:00")))
+        );
+        assert_eq!(
+            parse_time_bound("2024-03-01 12:30:15"),
+            Ok(TimeBound::At(at("2024-03-01T12:30:15")))
+        );
+        assert!(parse_time_bound("3y").is_err());
+        assert!(parse_time_bound("2024-13-01").is_err());
+        assert_eq!(
+            parse_time_bound("4294967295w"),
+            Err("time `4294967295w` is too long ago".to_string())
+        );
+    }
+
+    #[test]
+    fn time_windows() {
+        let bound = |s| parse_time_bound(s).unwrap();
+        assert!(check_time_window(bound("3d"), None).is_ok());
+        assert!(check_time_window(bound("3d"), Some(bound("1d"))).is_ok());
+        assert!(check_time_window(bound("2024-03-01"), Some(bound("2024-03-01"))).is_ok());
+        assert!(check_time_window(bound("2024-03-01"), Some(bound("now"))).is_ok());
+        assert_eq!(
+            check_time_window(bound("2d"), Some(bound("3d"))),
+            Err("the time window ends (3 days ago) before it starts (2 days ago)".to_string())
+        );
+        assert!(check_time_window(bound("2024-03-02"), Some(bound("2024-03-01T12:00"))).is_err());
+        assert!(check_time_window(bound("now"), Some(bound("2024-03-01"))).is_err());
+    }
 }
@@ -0,0 +1,85 @@
+use crossterm::event::KeyCode;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders};
+use tui_textarea::{CursorMove, Input, Key, TextArea};
+
+/// What to do with the text once the prompt is submitted
+#[derive(Clone, Debug)]
+pub enum PromptAction {
+    TimeWindow,
+    /// Signal to send to the job, `array` if it stands for several tasks
+    Signal {
+        job_id: String,
+        array: bool,
+    },
+    /// Submit the command line from the working directory of the original job
+    Resubmit {
+        workdir: String,
+    },
+    /// Directory to look for batch scripts in
+    Scripts,
+    /// `#SBATCH` option replacing the directive at `index`, or added to the form
+    Directive {
+        index: Option<usize>,
+    },
+    /// Regex to search in the log viewer, up from the cursor if `backward`
+    Search {
+        backward: bool,
+    },
+}
+
+pub enum PromptResult {
+    Editing,
+    Cancelled,
+    Submitted(String),
+}
+
+/// Single line text input, displayed over the current view
+pub struct Prompt<'a> {
+    pub textarea: TextArea<'a>,
+    pub action: PromptAction,
+}
+
+impl<'a> Prompt<'a> {
+    pub fn new(title: &str, text: &str, action: PromptAction) -> Self {
+        let mut textarea = TextArea::new(vec![text.to_string()]);
+        textarea.move_cursor(CursorMove::End);
+        textarea.set_cursor_line_style(Style::default());
+        textarea.set_block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title.to_string())
+                .title_bottom("[Enter] validate [Esc] cancel"),
+        );
+        Prompt { textarea, action }
+    }
+
+    pub fn with_placeholder(mut self, placeholder: &str) -> Self {
+        self.textarea.set_placeholder_text(placeholder);
+        self
+    }
+
+    pub fn text(&self) -> String {
+        self.textarea.lines().join("")
+    }
+
+    pub fn send_keycode(&mut self, keycode: KeyCode) -> PromptResult {
+        let key = match keycode {
+            KeyCode::Enter => return PromptResult::Submitted(self.text()),
+            KeyCode::Esc => return PromptResult::Cancelled,
+            KeyCode::Char(c) => Key::Char(c),
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            _ => return PromptResult::Editing,
+        };
+        self.textarea.input(Input {
+            key,
+            ..Default::default()
+        });
+        PromptResult::Editing
+    }
+}
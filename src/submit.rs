@@ -0,0 +1,192 @@
+//! Form built from the `#SBATCH` directives of a batch script
+use crate::jobs::shell::shell_quote;
+use std::path::Path;
+
+/// Option of a `#SBATCH` line, such as `--mem=4G` or `-c 8`
+#[derive(Clone, Debug)]
+pub struct Directive {
+    pub option: String,
+    pub value: Option<String>,
+    /// Changed in the form, so given to sbatch on the command line
+    pub overridden: bool,
+}
+
+impl Directive {
+    pub fn parse(text: &str) -> Option<Directive> {
+        let text = text.trim();
+        let (option, value) = if text.starts_with("--") {
+            match text.split_once(|c: char| c == '=' || c.is_whitespace()) {
+                Some((option, value)) => (option, value),
+                None => (text, ""),
+            }
+        } else if text.starts_with('-') && text.chars().count() >= 2 {
+            // short options take their value after a space, or right after the letter
+            let letter_end = text.char_indices().nth(2).map_or(text.len(), |(i, _)| i);
+            text.split_at(letter_end)
+        } else {
+            return None;
+        };
+        Some(Directive {
+            option: option.to_string(),
+            value: directive_value(value),
+            overridden: false,
+        })
+    }
+
+    /// Arguments giving the directive to sbatch, before the script name
+    fn args(&self) -> Vec<String> {
+        match (&self.value, self.option.starts_with("--")) {
+            (None, _) => vec![self.option.clone()],
+            (Some(value), true) => vec![format!("{}={}", self.option, value)],
+            (Some(value), false) => vec![self.option.clone(), value.clone()],
+        }
+    }
+
+    pub fn display(&self) -> String {
+        self.args().join(" ")
+    }
+}
+
+/// Value of a directive up to the first whitespace out of quotes, the quotes
+/// removed, like sbatch reads it, so that a trailing comment is left out
+fn directive_value(text: &str) -> Option<String> {
+    let text = text.trim_start();
+    if text.starts_with('#') {
+        return None;
+    }
+    let mut value = String::new();
+    let mut quote = None;
+    for c in text.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => value.push(c),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c.is_whitespace() => break,
+            None => value.push(c),
+        }
+    }
+    Some(value).filter(|v| !v.is_empty())
+}
+
+/// Directives of the script, read until its first command like sbatch does
+pub fn parse_directives(script: &str) -> Vec<Directive> {
+    let mut directives = vec![];
+    for line in script.lines().map(|l| l.trim()) {
+        if let Some(directive) = line.strip_prefix("#SBATCH") {
+            directives.extend(Directive::parse(directive));
+        } else if !line.is_empty() && !line.starts_with('#') {
+            break;
+        }
+    }
+    directives
+}
+
+pub struct SubmitForm {
+    pub path: String,
+    pub directives: Vec<Directive>,
+}
+
+impl SubmitForm {
+    pub fn new(path: &str, script: &str) -> Self {
+        SubmitForm {
+            path: path.to_string(),
+            directives: parse_directives(script),
+        }
+    }
+
+    /// Directory the script is submitted from
+    pub fn workdir(&self) -> String {
+        match Path::new(&self.path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.display().to_string(),
+            _ => ".".to_string(),
+        }
+    }
+
+    /// sbatch command line, the options on the command line taking precedence
+    /// over the directives of the script
+    pub fn command_line(&self) -> String {
+        let mut args = vec!["sbatch".to_string()];
+        self.directives
+            .iter()
+            .filter(|d| d.overridden)
+            .flat_map(|d| d.args())
+            .for_each(|arg| args.push(shell_quote(&arg)));
+        args.push(shell_quote(&self.path));
+        args.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(text: &str) -> (String, Option<String>) {
+        let directive = Directive::parse(text).unwrap();
+        (directive.option, directive.value)
+    }
+
+    fn pair(option: &str, value: Option<&str>) -> (String, Option<String>) {
+        (option.to_string(), value.map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn directives_stop_at_unquoted_whitespace() {
+        assert_eq!(parsed(" --mem=4G"), pair("--mem", Some("4G")));
+        assert_eq!(parsed(" --time 1:00:00"), pair("--time", Some("1:00:00")));
+        assert_eq!(
+            parsed(" --time=1:00:00  # one hour"),
+            pair("--time", Some("1:00:00"))
+        );
+        assert_eq!(
+            parsed(" --job-name=\"my run\" # quoted"),
+            pair("--job-name", Some("my run"))
+        );
+        assert_eq!(
+            parsed(" --exclusive  # whole node"),
+            pair("--exclusive", None)
+        );
+        assert_eq!(parsed(" -c 8"), pair("-c", Some("8")));
+        assert_eq!(parsed(" -c8 # cores"), pair("-c", Some("8")));
+        assert!(Directive::parse(" mem=4G").is_none());
+    }
+
+    #[test]
+    fn short_options_split_on_characters() {
+        assert_eq!(parsed("-é"), pair("-é", None));
+        assert_eq!(parsed("-Jé"), pair("-J", Some("é")));
+    }
+
+    #[test]
+    fn directives_end_at_the_first_command() {
+        let script = "#!/bin/bash
+#SBATCH -c 8
+
+# a comment
+#SBATCH --mem=4G
+echo hi
+#SBATCH --time=1:00
+";
+        let options: Vec<_> = parse_directives(script)
+            .into_iter()
+            .map(|d| d.option)
+            .collect();
+        assert_eq!(options, ["-c", "--mem"]);
+    }
+
+    #[test]
+    fn command_line_gives_overridden_directives() {
+        let mut form = SubmitForm::new("runs/my run.sh", "#SBATCH -c 8\n#SBATCH --mem=4G\n");
+        form.directives[1].value = Some("8G".to_string());
+        form.directives[1].overridden = true;
+        form.directives.push(Directive {
+            option: "-J".to_string(),
+            value: Some("it's".to_string()),
+            overridden: true,
+        });
+        assert_eq!(
+            form.command_line(),
+            "sbatch '--mem=8G' '-J' 'it'\\''s' 'runs/my run.sh'"
+        );
+        assert_eq!(form.workdir(), "runs");
+    }
+}
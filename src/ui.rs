@@ -1,20 +1,28 @@
+use crate::ansi::AnsiMode;
 use crate::app::App;
 use crate::app::DisplayState;
-use crate::app::{DESCRIPTION_JOB, DESCRIPTION_LOG};
+use crate::app::{
+    DESCRIPTION_DETAILS, DESCRIPTION_EDITOR, DESCRIPTION_JOB, DESCRIPTION_LOG, DESCRIPTION_SCRIPTS,
+    DESCRIPTION_SUBMIT,
+};
 use crate::editor::Editor;
-use crate::jobs::job_parser::JobFields;
+use crate::job_query_info::{JobQueryInfo, TaskFilter};
+use crate::jobs::job_source::JobDetails;
+use crate::submit::SubmitForm;
+use base64::Engine;
 use ratatui::prelude::*;
-use ratatui::widgets::block::Position;
+use ratatui::widgets::block::{Position, Title};
 use ratatui::widgets::Block;
 use ratatui::widgets::Borders;
+use ratatui::widgets::Clear;
 use ratatui::widgets::List;
 use ratatui::widgets::ListItem;
-use ratatui::widgets::{Row, Table};
+use ratatui::widgets::ListState;
+use ratatui::widgets::Paragraph;
 use ratatui::Frame;
+use std::io::Write;
 use tui_popup::Popup;
 
-use tracing::info;
-
 struct ColoredString {
     string: String,
     color: Color,
@@ -31,13 +39,18 @@ fn display_jobs(frame: &mut Frame, app: &App) {
             })
             .collect();
         let list_items = build_list(&colored_strings, app.highlighted);
-        let legend = DESCRIPTION_JOB.to_string();
-        let list_widget = build_widget(list_items, &legend);
+        let legend =
+            DESCRIPTION_JOB.to_string() + &refresh_status(app, job_info) + &loading_status(app);
+        let mut title = format!("Jobs: {}", app.fetch_time.description());
+        if job_info.task_filter != TaskFilter::All {
+            title += &format!(" (array tasks: {})", job_info.task_filter.description());
+        }
+        let list_widget = build_widget(list_items, &title, &legend);
         frame.render_widget(list_widget, frame.size());
     }
 }
 
-fn build_list(lines: &[ColoredString], highlighted: Option<usize>) -> Vec<ListItem> {
+fn build_list(lines: &[ColoredString], highlighted: Option<usize>) -> Vec<ListItem<'_>> {
     lines
         .iter()
         .enumerate()
@@ -55,10 +68,11 @@ fn build_list(lines: &[ColoredString], highlighted: Option<usize>) -> Vec<ListIt
         .collect()
 }
 
-fn build_widget<'a>(list_items: Vec<ListItem<'a>>, text: &'a str) -> List<'a> {
+fn build_widget<'a>(list_items: Vec<ListItem<'a>>, title: &'a str, text: &'a str) -> List<'a> {
     List::new(list_items)
         .block(
             Block::default()
+                .title(Title::from(title).position(Position::Top))
                 .title(text)
                 .title_position(Position::Bottom)
                 .borders(Borders::ALL),
@@ -69,12 +83,46 @@ fn build_widget<'a>(list_items: Vec<ListItem<'a>>, text: &'a str) -> List<'a> {
 }
 
 fn display_editor(frame: &mut Frame, editor: &Editor) {
-    let widget = editor.textarea.widget();
-    frame.render_widget(widget, frame.size());
+    let mut title = editor.path.clone();
+    match editor.ansi_mode {
+        AnsiMode::Render => (),
+        AnsiMode::Strip => title += " (colours stripped)",
+        AnsiMode::Raw => title += " (raw)",
+    }
+    if !editor.is_whole() {
+        title += &format!(
+            " [bytes {}-{} of {}]",
+            editor.start, editor.end, editor.file_size
+        );
+    }
+    if editor.follow {
+        // moving up pauses the scrolling, not the reading
+        title += if editor.at_bottom() {
+            " (following)"
+        } else {
+            " (following, paused)"
+        };
+    }
+    let mut block = Block::default()
+        .title(Title::from(title).position(Position::Top))
+        .title(DESCRIPTION_EDITOR)
+        .title_position(Position::Bottom)
+        .borders(Borders::ALL);
+    if let Some(status) = editor.search_status() {
+        // above, the legend taking the bottom line
+        let status = Title::from(status)
+            .position(Position::Top)
+            .alignment(Alignment::Right);
+        block = block.title(status);
+    }
+    let area = block.inner(frame.size());
+    frame.render_widget(block, frame.size());
+    let lines = editor.view_lines(area.width, area.height);
+    frame.render_widget(Paragraph::new(lines), area);
 }
 
-fn display_details(frame: &mut Frame, app: &App, log_files: &[String]) {
-    let colored_strings: Vec<_> = log_files
+fn display_details(frame: &mut Frame, app: &App, files: &[String], title: &str, description: &str) {
+    let colored_strings: Vec<_> = files
         .iter()
         .map(|s| ColoredString {
             string: s.clone(),
@@ -82,7 +130,74 @@ fn display_details(frame: &mut Frame, app: &App, log_files: &[String]) {
         })
         .collect();
     let list_items = build_list(&colored_strings, app.highlighted);
-    let list_widget = build_widget(list_items, DESCRIPTION_LOG);
+    let legend = description.to_string() + &loading_status(app);
+    let list_widget = build_widget(list_items, title, &legend);
+    frame.render_widget(list_widget, frame.size());
+}
+
+static MAX_NAME_WIDTH: usize = 24;
+
+fn display_job_details(frame: &mut Frame, app: &App, details: &JobDetails) {
+    let area = frame.size();
+    let name_width = details
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .min(MAX_NAME_WIDTH);
+    // borders, highlight symbol and spacing
+    let value_width = (area.width as usize).saturating_sub(name_width + 6).max(10);
+    let colored_strings: Vec<_> = details
+        .iter()
+        .map(|(name, value)| {
+            // long values such as the command line are wrapped rather than cut
+            let lines: Vec<_> = wrap(value, value_width)
+                .iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    let name = if i == 0 { name.as_str() } else { "" };
+                    format!("{:width$}  {}", name, line, width = name_width)
+                })
+                .collect();
+            ColoredString {
+                string: lines.join("\n"),
+                color: Color::White,
+            }
+        })
+        .collect();
+    let list_items = build_list(&colored_strings, app.highlighted);
+    let legend = DESCRIPTION_DETAILS.to_string() + &loading_status(app);
+    let list_widget = build_widget(list_items, "Job details", &legend);
+    // the list scrolls to keep the highlighted field visible
+    let mut state = ListState::default().with_selected(app.highlighted);
+    frame.render_stateful_widget(list_widget, area, &mut state);
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars.chunks(width).map(String::from_iter).collect()
+}
+
+fn display_submit(frame: &mut Frame, app: &App, form: &SubmitForm) {
+    let colored_strings: Vec<_> = form
+        .directives
+        .iter()
+        .map(|directive| ColoredString {
+            string: directive.display(),
+            // overridden directives are given to sbatch
+            color: if directive.overridden {
+                Color::Yellow
+            } else {
+                Color::White
+            },
+        })
+        .collect();
+    let list_items = build_list(&colored_strings, app.highlighted);
+    let title = format!("Submit {}", form.path);
+    let list_widget = build_widget(list_items, &title, DESCRIPTION_SUBMIT);
     frame.render_widget(list_widget, frame.size());
 }
 
@@ -110,13 +225,101 @@ pub fn ui(frame: &mut Frame, app: &App) {
     match &app.display_state {
         DisplayState::Editor(ref editor) => display_editor(frame, editor),
         DisplayState::Jobs(_) => display_jobs(frame, app),
-        DisplayState::Logs(ref details) => display_details(frame, app, details),
-        DisplayState::Empty => (),
+        DisplayState::Logs(ref details) => {
+            display_details(frame, app, details, "Log files", DESCRIPTION_LOG)
+        }
+        DisplayState::Details(ref details) => display_job_details(frame, app, details),
+        DisplayState::Scripts(ref scripts) => {
+            display_details(frame, app, scripts, "Batch scripts", DESCRIPTION_SCRIPTS)
+        }
+        DisplayState::Submit(ref form) => display_submit(frame, app, form),
+        DisplayState::Empty => display_loading(frame, app),
         // DisplayState::Report(job_detail) => display_report(frame, job_detail),
     }
+    display_prompt(frame, app);
+    display_confirm(frame, app);
+    display_menu(frame, app);
     display_popup(frame, app);
 }
 
+static SPINNER: [char; 8] = ['⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷'];
+
+fn loading_status(app: &App) -> String {
+    if !app.is_loading() {
+        return String::new();
+    }
+    let text = match app.pending_view {
+        Some(pending) => pending.text,
+        None => "jobs",
+    };
+    format!(" {} loading {}", SPINNER[app.tick % SPINNER.len()], text)
+}
+
+fn refresh_status(app: &App, job_info: &JobQueryInfo) -> String {
+    let waiting = if job_info.changed {
+        app.time_to_next_fetch()
+    } else if job_info.refresh {
+        app.time_to_refresh().max(app.time_to_next_fetch())
+    } else {
+        return String::new();
+    };
+    if app.pending_jobs.is_some() || waiting.is_zero() {
+        return String::new();
+    }
+    // round up, so that the countdown never shows 0s before refreshing
+    format!(" refresh in {}s", waiting.as_millis().div_ceil(1000))
+}
+
+fn display_loading(frame: &mut Frame, app: &App) {
+    // jobs may also be waiting for the minimum refresh interval
+    let status = format!(" {} loading jobs", SPINNER[app.tick % SPINNER.len()]);
+    let block = Block::default()
+        .title(status)
+        .title_position(Position::Bottom)
+        .borders(Borders::ALL);
+    frame.render_widget(block, frame.size());
+}
+
+fn display_prompt(frame: &mut Frame, app: &App) {
+    if let Some(ref prompt) = app.prompt {
+        let area = centered_rect(frame.size(), 60, 3);
+        frame.render_widget(Clear, area);
+        frame.render_widget(prompt.textarea.widget(), area);
+    }
+}
+
+/// Area of `percent_x` of the width and `height` lines in the middle of `area`
+fn centered_rect(area: Rect, percent_x: u16, height: u16) -> Rect {
+    let vertical = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Length(height),
+        Constraint::Fill(1),
+    ])
+    .split(area);
+    Layout::horizontal([
+        Constraint::Percentage((100 - percent_x) / 2),
+        Constraint::Percentage(percent_x),
+        Constraint::Percentage((100 - percent_x) / 2),
+    ])
+    .split(vertical[1])[1]
+}
+
+fn display_confirm(frame: &mut Frame, app: &App) {
+    if let Some(ref confirm) = app.confirm {
+        let text = confirm.action.description() + "?";
+        let popup = Popup::new(text, confirm.legend());
+        frame.render_widget(popup.to_widget(), frame.size());
+    }
+}
+
+fn display_menu(frame: &mut Frame, app: &App) {
+    if let Some(ref menu) = app.menu {
+        let title = format!("Actions on {}, any other key to close", menu.job_id);
+        let popup = Popup::new(menu.text(), title.as_str());
+        frame.render_widget(popup.to_widget(), frame.size());
+    }
+}
+
 fn display_popup(frame: &mut Frame, app: &App) {
     if let Some(ref my_popup) = app.popup {
         let area = frame.size();
@@ -125,6 +328,15 @@ fn display_popup(frame: &mut Frame, app: &App) {
     }
 }
 
+/// Sets the clipboard of the terminal with an OSC 52 sequence, which also works
+/// through ssh
+pub fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()
+}
+
 pub trait Colorable {
     fn to_color(&self) -> Color;
 }
@@ -143,7 +355,7 @@ impl Colorable for LineType {
     }
 }
 
-fn build_list_item(c_str: &ColoredString, line_type: LineType) -> ListItem {
+fn build_list_item(c_str: &ColoredString, line_type: LineType) -> ListItem<'_> {
     let bg_color = line_type.to_color();
     ListItem::new(c_str.string.clone()).style(Style::default().fg(c_str.color).bg(bg_color))
 }
@@ -0,0 +1,372 @@
+use crate::jobs::job_action::JobAction;
+use crate::jobs::job_parser::JobFields;
+use crate::jobs::job_source::{JobDetails, JobSource, TimeWindow};
+use crate::jobs::log_page::{FileSearch, LogPage, PageRange};
+use color_eyre::eyre::{Report, Result};
+use std::any::Any;
+use std::mem::discriminant;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Name of the worker thread, whose panics are caught and reported
+pub static THREAD_NAME: &str = "worker";
+/// Time given to the source to finish its request and close on quit, before
+/// the thread is left behind
+static CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub enum Request {
+    Jobs {
+        window: TimeWindow,
+    },
+    Logs {
+        job: Box<JobFields>,
+    },
+    /// Opens the log at its last page
+    ReadLog {
+        path: String,
+    },
+    /// Another page of the open log, or what was appended to a followed one
+    LogPage {
+        path: String,
+        range: PageRange,
+    },
+    /// Next match in the open log, past the loaded lines
+    SearchLog {
+        path: String,
+        search: FileSearch,
+    },
+    Details {
+        job_id: String,
+    },
+    Scripts {
+        dir: String,
+    },
+    ReadScript {
+        path: String,
+    },
+    Action(JobAction),
+}
+
+pub enum Response {
+    Jobs(Result<Vec<JobFields>>),
+    Logs(Result<Vec<String>>),
+    ReadLog {
+        path: String,
+        page: Result<LogPage>,
+    },
+    LogPage {
+        path: String,
+        range: PageRange,
+        page: Result<LogPage>,
+    },
+    SearchLog {
+        path: String,
+        found: Result<Option<u64>>,
+    },
+    Details(Result<JobDetails>),
+    Scripts(Result<Vec<String>>),
+    ReadScript {
+        path: String,
+        script: Result<String>,
+    },
+    Action(Result<String>),
+}
+
+/// Runs the requests to the job source on a background thread, so that a slow
+/// source does not freeze the UI. Every request gets an id, sent back with its response.
+pub struct Worker {
+    sender: Option<Sender<(u64, Request)>>,
+    receiver: Receiver<(u64, Response)>,
+    /// Responses to the requests the worker could not take, having stopped
+    failed: Vec<(u64, Response)>,
+    next_id: u64,
+}
+
+impl Worker {
+    pub fn new(source: Box<dyn JobSource>) -> Result<Self> {
+        let (sender, requests) = channel();
+        let (responses, receiver) = channel();
+        // the thread is detached, the responses channel telling when it ends
+        std::thread::Builder::new()
+            .name(THREAD_NAME.to_string())
+            .spawn(move || run(source, requests, responses))
+            .map_err(|e| Report::msg(format!("Could not start the worker thread: {}", e)))?;
+        Ok(Worker {
+            sender: Some(sender),
+            receiver,
+            failed: vec![],
+            next_id: 0,
+        })
+    }
+
+    /// Sends the request, answered with an error once the worker has stopped
+    pub fn send(&mut self, request: Request) -> u64 {
+        self.next_id += 1;
+        let sent = match self.sender {
+            Some(ref sender) => sender.send((self.next_id, request)).map_err(|e| e.0 .1),
+            None => Err(request),
+        };
+        if let Err(request) = sent {
+            let error = Report::msg("The worker stopped, restart sboard to fetch jobs again");
+            self.failed.push((self.next_id, failed(&request, error)));
+        }
+        self.next_id
+    }
+
+    pub fn try_recv(&mut self) -> Option<(u64, Response)> {
+        if !self.failed.is_empty() {
+            return Some(self.failed.remove(0));
+        }
+        // a stopped worker has no more responses
+        self.receiver.try_recv().ok()
+    }
+
+    /// Lets the worker finish its request and close the source, giving up
+    /// after CLOSE_TIMEOUT so that a slow command does not delay quitting
+    pub fn close(&mut self) {
+        self.sender = None;
+        let deadline = Instant::now() + CLOSE_TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.receiver.recv_timeout(timeout) {
+                Ok(_) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    info!("leaving the worker behind, its request still running");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn run(
+    source: Box<dyn JobSource>,
+    requests: Receiver<(u64, Request)>,
+    responses: Sender<(u64, Response)>,
+) {
+    while let Ok(first) = requests.recv() {
+        let mut queue = vec![first];
+        queue.extend(requests.try_iter());
+        for (i, (id, request)) in queue.iter().enumerate() {
+            // a newer request of the same kind cancels the queued ones,
+            // except for actions which are all run
+            let superseded = !matches!(request, Request::Action(_))
+                && queue[i + 1..]
+                    .iter()
+                    .any(|(_, r)| discriminant(r) == discriminant(request));
+            if superseded {
+                info!("skipping stale request {}", id);
+                continue;
+            }
+            // a panicking source fails the request instead of the whole UI
+            let response = panic::catch_unwind(AssertUnwindSafe(|| respond(&*source, request)))
+                .unwrap_or_else(|payload| failed(request, panic_message(payload)));
+            if responses.send((*id, response)).is_err() {
+                break;
+            }
+        }
+    }
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| source.close())) {
+        info!("closing the source failed: {}", panic_message(payload));
+    }
+}
+
+/// Answer of the source to the request
+fn respond(source: &dyn JobSource, request: &Request) -> Response {
+    match request {
+        Request::Jobs { window } => Response::Jobs(source.list_jobs(window)),
+        Request::Logs { job } => Response::Logs(source.list_logs(job)),
+        Request::ReadLog { path } => Response::ReadLog {
+            path: path.clone(),
+            page: source.read_log_page(path, PageRange::Tail),
+        },
+        Request::LogPage { path, range } => Response::LogPage {
+            path: path.clone(),
+            range: *range,
+            page: source.read_log_page(path, *range),
+        },
+        Request::SearchLog { path, search } => Response::SearchLog {
+            path: path.clone(),
+            found: source.search_log(path, search),
+        },
+        Request::Details { job_id } => Response::Details(source.job_details(job_id)),
+        Request::Scripts { dir } => Response::Scripts(source.list_scripts(dir)),
+        Request::ReadScript { path } => Response::ReadScript {
+            path: path.clone(),
+            script: source.read_log(path),
+        },
+        Request::Action(action) => Response::Action(source.run_action(action)),
+    }
+}
+
+/// Response to the request failing with the error
+fn failed(request: &Request, error: Report) -> Response {
+    match request {
+        Request::Jobs { .. } => Response::Jobs(Err(error)),
+        Request::Logs { .. } => Response::Logs(Err(error)),
+        Request::ReadLog { path } => Response::ReadLog {
+            path: path.clone(),
+            page: Err(error),
+        },
+        Request::LogPage { path, range } => Response::LogPage {
+            path: path.clone(),
+            range: *range,
+            page: Err(error),
+        },
+        Request::SearchLog { path, .. } => Response::SearchLog {
+            path: path.clone(),
+            found: Err(error),
+        },
+        Request::Details { .. } => Response::Details(Err(error)),
+        Request::Scripts { .. } => Response::Scripts(Err(error)),
+        Request::ReadScript { path } => Response::ReadScript {
+            path: path.clone(),
+            script: Err(error),
+        },
+        Request::Action(_) => Response::Action(Err(error)),
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> Report {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown error".to_string(),
+        },
+    };
+    Report::msg(format!("The request failed: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::job_source::TimeBound;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// Source listing jobs after `delay`, panicking when `panics` is set
+    struct TestSource {
+        delay: Duration,
+        panics: bool,
+        closed: Arc<AtomicBool>,
+    }
+
+    impl JobSource for TestSource {
+        fn list_jobs(&self, _window: &TimeWindow) -> Result<Vec<JobFields>> {
+            std::thread::sleep(self.delay);
+            if self.panics {
+                panic!("sacct output is empty");
+            }
+            Ok(vec![JobFields::header()?])
+        }
+        fn list_logs(&self, _job: &JobFields) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+        fn read_log(&self, _path: &str) -> Result<String> {
+            Err(Report::msg("no log"))
+        }
+        fn read_log_page(&self, _path: &str, _range: PageRange) -> Result<LogPage> {
+            Err(Report::msg("no log"))
+        }
+        fn search_log(&self, _path: &str, _search: &FileSearch) -> Result<Option<u64>> {
+            Ok(None)
+        }
+        fn list_scripts(&self, _dir: &str) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+        fn job_details(&self, _job_id: &str) -> Result<JobDetails> {
+            Ok(vec![])
+        }
+        fn run_action(&self, _action: &JobAction) -> Result<String> {
+            Ok(String::new())
+        }
+        fn close(&self) {
+            self.closed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn worker(delay: Duration, panics: bool) -> (Worker, Arc<AtomicBool>) {
+        let closed = Arc::new(AtomicBool::new(false));
+        let source = TestSource {
+            delay,
+            panics,
+            closed: closed.clone(),
+        };
+        (Worker::new(Box::new(source)).unwrap(), closed)
+    }
+
+    fn jobs() -> Request {
+        Request::Jobs {
+            window: TimeWindow {
+                start: TimeBound::HoursAgo(24),
+                end: None,
+            },
+        }
+    }
+
+    fn wait(worker: &mut Worker) -> (u64, Response) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if let Some(response) = worker.try_recv() {
+                return response;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("no response from the worker");
+    }
+
+    #[test]
+    fn responses_carry_the_request_id() {
+        let (mut worker, closed) = worker(Duration::ZERO, false);
+        let id = worker.send(jobs());
+        let (response_id, response) = wait(&mut worker);
+        assert_eq!(response_id, id);
+        assert!(matches!(response, Response::Jobs(Result::Ok(ref rows)) if rows.len() == 1));
+        worker.close();
+        assert!(closed.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn panic_fails_the_request_only() {
+        let (mut worker, _) = worker(Duration::ZERO, true);
+        let id = worker.send(jobs());
+        let Response::Jobs(Err(error)) = wait(&mut worker).1 else {
+            panic!("the request should fail");
+        };
+        assert_eq!(
+            error.to_string(),
+            "The request failed: sacct output is empty"
+        );
+        // the worker goes on with the next requests
+        let next = worker.send(Request::Details {
+            job_id: "1001".to_string(),
+        });
+        assert!(next > id);
+        assert_eq!(wait(&mut worker).0, next);
+    }
+
+    #[test]
+    fn close_does_not_wait_for_a_slow_request() {
+        let (mut worker, closed) = worker(Duration::from_secs(10), false);
+        worker.send(jobs());
+        let start = Instant::now();
+        worker.close();
+        assert!(start.elapsed() < CLOSE_TIMEOUT + Duration::from_secs(1));
+        assert!(!closed.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn requests_to_a_stopped_worker_fail() {
+        let (mut worker, _) = worker(Duration::ZERO, false);
+        worker.close();
+        let id = worker.send(jobs());
+        let (response_id, response) = wait(&mut worker);
+        assert_eq!(response_id, id);
+        assert!(matches!(response, Response::Jobs(Err(_))));
+        assert!(worker.try_recv().is_none());
+    }
+}
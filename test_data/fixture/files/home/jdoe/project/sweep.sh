@@ -0,0 +1,8 @@
+#!/bin/bash
+#SBATCH -J sweep
+#SBATCH --partition=cpu
+#SBATCH --output=logs/sweep_%A_%a.out
+#SBATCH --array=1-4,6-8
+#SBATCH --time 00:30:00
+
+python sweep.py --index "$SLURM_ARRAY_TASK_ID"
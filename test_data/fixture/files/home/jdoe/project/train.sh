@@ -0,0 +1,11 @@
+#!/bin/bash
+#SBATCH --job-name=train_model
+#SBATCH --partition=gpu
+#SBATCH --gres=gpu:1
+#SBATCH -c 8
+#SBATCH --mem=32G
+#SBATCH --time=12:00:00
+
+# the directives end at the first command
+module load python/3.11
+python train.py --epochs 20